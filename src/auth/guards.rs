@@ -0,0 +1,60 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+use crate::config::Config;
+
+use super::role::Role;
+
+pub struct ReadOnly;
+
+pub struct Editor;
+
+pub struct Admin;
+
+// A missing or unknown token is unauthorized, a known one without the required role is forbidden.
+fn authorize(req: &Request<'_>, required_role: Role) -> Outcome<(), ()> {
+    let config = match req.rocket().state::<Config>() {
+        Some(config) => config,
+        None => return Outcome::Error((Status::InternalServerError, ())),
+    };
+
+    let token = req
+        .headers()
+        .get_one("Authorization")
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .map(|token| token.trim());
+
+    match token.and_then(|token| config.tokens.get(token)) {
+        Some(role) if *role >= required_role => Outcome::Success(()),
+        Some(_) => Outcome::Error((Status::Forbidden, ())),
+        None => Outcome::Error((Status::Unauthorized, ())),
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ReadOnly {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        authorize(req, Role::ReadOnly).map(|_| ReadOnly)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Editor {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        authorize(req, Role::Editor).map(|_| Editor)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        authorize(req, Role::Admin).map(|_| Admin)
+    }
+}
@@ -0,0 +1,2 @@
+pub mod guards;
+pub mod role;
@@ -0,0 +1,25 @@
+use std::str::FromStr;
+
+// Roles are ordered, every role is granted what the lower ones can do.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    // Game servers pulling template archives.
+    ReadOnly,
+    // Pushing content and creating parents, templates and maps.
+    Editor,
+    // Deleting and building.
+    Admin,
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "read-only" => Ok(Role::ReadOnly),
+            "editor" => Ok(Role::Editor),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("Unknown role {}", value)),
+        }
+    }
+}
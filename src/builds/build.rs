@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::maps::reference::ResolvedMap;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Build {
+    pub id: String,
+    pub template: String,
+    #[serde(default)]
+    pub mode: Mode,
+    pub version: Option<u32>,
+    pub number: Option<u32>,
+    pub image: Option<String>,
+    pub digest: Option<String>,
+    // The map versions copied into a baked image.
+    #[serde(default)]
+    pub maps: Option<Vec<ResolvedMap>>,
+    pub status: Status,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Queued,
+    Running,
+    Pushed,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, FromFormField, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    // The container downloads the template archive from the API when it starts.
+    #[default]
+    Remote,
+    // The template content and its maps are copied into the image.
+    Baked,
+}
+
+impl Build {
+    pub fn new(template: &str, mode: Mode, version: Option<u32>) -> Build {
+        Build {
+            id: uuid::Uuid::new_v4().to_string(),
+            template: template.to_string(),
+            mode,
+            version,
+            number: None,
+            image: None,
+            digest: None,
+            maps: None,
+            status: Status::Queued,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            error: None,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::io::Error;
+use std::path::Path;
+use std::sync::Mutex;
+
+use rocket::serde::json::serde_json;
+
+use crate::{global, templates};
+
+use super::build::{Build, Status};
+use super::log;
+
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn get_history_file_path(template_name: &str) -> String {
+    format!(
+        "{}/history.epsilon",
+        log::get_build_logs_path(template_name)
+    )
+}
+
+pub fn get_history(template_name: &str) -> Result<Vec<Build>, Error> {
+    let history_file_path_str = get_history_file_path(template_name);
+
+    if !Path::new(&history_file_path_str).exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(history_file_path_str)?;
+
+    Ok(serde_json::from_reader(&file)?)
+}
+
+fn write_history(template_name: &str, history: &Vec<Build>) -> Result<(), Error> {
+    log::create_build_logs_dir(template_name)?;
+
+    let file = File::create(get_history_file_path(template_name))?;
+
+    Ok(serde_json::to_writer_pretty(file, history)?)
+}
+
+fn get_template_names() -> Result<Vec<String>, Error> {
+    let template_names = std::fs::read_dir(global::TEMPLATES_DIR)?
+        .filter_map(|dir| dir.ok())
+        .filter(|dir| dir.path().is_dir())
+        .filter_map(|dir| dir.file_name().to_str().map(String::from))
+        .filter(|name| templates::manager::template_exist(name))
+        .collect();
+
+    Ok(template_names)
+}
+
+pub fn find_build(id: &str) -> Result<Option<Build>, Error> {
+    for template_name in get_template_names()? {
+        let history = get_history(&template_name)?;
+
+        if let Some(mut build) = history.into_iter().find(|build| build.id == id) {
+            // A build made before a rename still holds the previous name.
+            build.template = template_name;
+
+            return Ok(Some(build));
+        }
+    }
+
+    Ok(None)
+}
+
+// Gives the build the next number of its template and records it, so concurrent builds of the
+// same template never share a number.
+pub fn start_build(build: &mut Build) -> Result<(), Error> {
+    let _guard = HISTORY_LOCK.lock().unwrap();
+    let mut history = get_history(&build.template)?;
+
+    let last_number = history.iter().filter_map(|build| build.number).max();
+
+    build.number = Some(last_number.unwrap_or(0) + 1);
+
+    match history.iter_mut().find(|entry| entry.id == build.id) {
+        Some(entry) => *entry = build.clone(),
+        None => history.push(build.clone()),
+    }
+
+    write_history(&build.template, &history)
+}
+
+pub fn save_build(build: &Build) -> Result<(), Error> {
+    let _guard = HISTORY_LOCK.lock().unwrap();
+    let mut history = get_history(&build.template)?;
+
+    match history.iter_mut().find(|entry| entry.id == build.id) {
+        Some(entry) => *entry = build.clone(),
+        None => history.push(build.clone()),
+    }
+
+    write_history(&build.template, &history)
+}
+
+// Builds still queued or running in a history come from a previous process and will never end.
+pub fn fail_interrupted_builds() -> Result<(), Error> {
+    let _guard = HISTORY_LOCK.lock().unwrap();
+
+    for template_name in get_template_names()? {
+        let template_name = template_name.as_str();
+        let mut history = get_history(template_name)?;
+        let mut interrupted = false;
+
+        for build in history.iter_mut() {
+            if build.status == Status::Queued || build.status == Status::Running {
+                build.status = Status::Failed;
+                build.error = Some(String::from("The build has been interrupted."));
+                interrupted = true;
+            }
+        }
+
+        if interrupted {
+            write_history(template_name, &history)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn move_builds(template_name: &str, new_template_name: &str) -> Result<(), Error> {
+    let _guard = HISTORY_LOCK.lock().unwrap();
+    let build_logs_path_str = log::get_build_logs_path(template_name);
+
+    if template_name == new_template_name || !Path::new(&build_logs_path_str).exists() {
+        return Ok(());
+    }
+
+    std::fs::rename(
+        build_logs_path_str,
+        log::get_build_logs_path(new_template_name),
+    )
+}
+
+pub fn delete_builds(template_name: &str) -> Result<(), Error> {
+    let _guard = HISTORY_LOCK.lock().unwrap();
+    let build_logs_path_str = log::get_build_logs_path(template_name);
+
+    if !Path::new(&build_logs_path_str).exists() {
+        return Ok(());
+    }
+
+    std::fs::remove_dir_all(build_logs_path_str)
+}
@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind, Write};
+use std::sync::Mutex;
+
+use rocket::tokio::sync::broadcast::{self, Receiver, Sender};
+
+use crate::templates;
+
+// The builds of a template live next to its directory, template names can't have the reserved
+// extension so they never collide with one.
+const BUILD_LOGS_DIR_EXTENSION: &str = ".builds.epsilon";
+
+pub fn get_build_logs_path(template_name: &str) -> String {
+    format!(
+        "{}{}",
+        templates::manager::get_template_path(template_name),
+        BUILD_LOGS_DIR_EXTENSION
+    )
+}
+
+pub fn get_build_log_file_path(template_name: &str, id: &str) -> String {
+    format!("{}/{}.log", get_build_logs_path(template_name), id)
+}
+
+// Fails if the template doesn't exist anymore, its builds would otherwise outlive it.
+pub fn create_build_logs_dir(template_name: &str) -> Result<(), Error> {
+    if !templates::manager::template_exist(template_name) {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("The template {} doesn't exist.", template_name),
+        ));
+    }
+
+    match std::fs::create_dir(get_build_logs_path(template_name)) {
+        Err(err) if err.kind() != ErrorKind::AlreadyExists => Err(err),
+        _ => Ok(()),
+    }
+}
+
+pub struct BuildLog {
+    inner: Mutex<BuildLogInner>,
+}
+
+struct BuildLogInner {
+    lines: Vec<String>,
+    file: File,
+    sender: Option<Sender<String>>,
+}
+
+impl BuildLog {
+    pub fn create(template_name: &str, id: &str) -> Result<BuildLog, Error> {
+        create_build_logs_dir(template_name)?;
+
+        let file = File::create(get_build_log_file_path(template_name, id))?;
+        let (sender, _) = broadcast::channel(1024);
+
+        Ok(BuildLog {
+            inner: Mutex::new(BuildLogInner {
+                lines: Vec::new(),
+                file,
+                sender: Some(sender),
+            }),
+        })
+    }
+
+    pub fn push(&self, message: &str) {
+        let mut inner = self.inner.lock().unwrap();
+
+        for line in message.lines() {
+            let line = line.trim_end();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let _ = writeln!(inner.file, "{}", line);
+
+            if let Some(sender) = &inner.sender {
+                let _ = sender.send(line.to_string());
+            }
+
+            inner.lines.push(line.to_string());
+        }
+    }
+
+    pub fn finish(&self) {
+        let mut inner = self.inner.lock().unwrap();
+
+        let _ = inner.file.flush();
+        inner.sender = None;
+    }
+
+    // Returns the lines written so far and, while the build is still running, a receiver for the
+    // following ones. Both are taken under the same lock so no line is missed or sent twice.
+    pub fn subscribe(&self) -> (Vec<String>, Option<Receiver<String>>) {
+        self.subscribe_from(0)
+    }
+
+    // Same as `subscribe`, skipping the lines already received, used to catch up on a receiver
+    // that lagged behind.
+    pub fn subscribe_from(&self, index: usize) -> (Vec<String>, Option<Receiver<String>>) {
+        let inner = self.inner.lock().unwrap();
+        let receiver = inner.sender.as_ref().map(|sender| sender.subscribe());
+        let lines = inner.lines.get(index..).unwrap_or_default().to_vec();
+
+        (lines, receiver)
+    }
+}
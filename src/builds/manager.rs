@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::io::Error;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use rocket::tokio;
+use rocket::tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::config::Config;
+use crate::templates;
+use crate::templates::utils::BuiltImage;
+
+use super::build::{Build, Mode, Status};
+use super::history;
+use super::log::BuildLog;
+
+struct Job {
+    build: Build,
+    log: Arc<BuildLog>,
+}
+
+type Builds = Arc<Mutex<HashMap<String, Job>>>;
+
+#[derive(Clone)]
+pub struct BuildQueue {
+    builds: Builds,
+    sender: UnboundedSender<String>,
+}
+
+impl BuildQueue {
+    pub fn new(config: &Config) -> Result<BuildQueue, Error> {
+        history::fail_interrupted_builds()?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let builds: Builds = Arc::new(Mutex::new(HashMap::new()));
+        let receiver = Arc::new(tokio::sync::Mutex::new(receiver));
+        let config = Arc::new(config.clone());
+
+        for _ in 0..config.build_workers.max(1) {
+            tokio::spawn(run_worker(builds.clone(), receiver.clone(), config.clone()));
+        }
+
+        Ok(BuildQueue { builds, sender })
+    }
+
+    pub fn enqueue(
+        &self,
+        template_name: &str,
+        mode: Mode,
+        version: Option<u32>,
+    ) -> Result<Build, Error> {
+        let build = Build::new(template_name, mode, version);
+        let log = Arc::new(BuildLog::create(template_name, &build.id)?);
+
+        // Recorded right away, a build still queued when the process stops is then found and
+        // marked as interrupted on the next start.
+        history::save_build(&build)?;
+
+        self.builds.lock().unwrap().insert(
+            build.id.clone(),
+            Job {
+                build: build.clone(),
+                log,
+            },
+        );
+
+        self.sender
+            .send(build.id.clone())
+            .map_err(|_err| Error::other("The build queue is closed."))?;
+
+        Ok(build)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<Build>, Error> {
+        let build = self
+            .builds
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|job| job.build.clone());
+
+        match build {
+            Some(build) => Ok(Some(build)),
+            None => history::find_build(id),
+        }
+    }
+
+    pub fn get_log(&self, id: &str) -> Option<Arc<BuildLog>> {
+        let builds = self.builds.lock().unwrap();
+
+        builds.get(id).map(|job| job.log.clone())
+    }
+}
+
+fn update_build(
+    builds: &Builds,
+    id: &str,
+    update: impl FnOnce(&mut Build),
+) -> Option<(Build, Arc<BuildLog>)> {
+    let mut builds = builds.lock().unwrap();
+    let job = builds.get_mut(id)?;
+
+    update(&mut job.build);
+
+    Some((job.build.clone(), job.log.clone()))
+}
+
+async fn run_worker(
+    builds: Builds,
+    receiver: Arc<tokio::sync::Mutex<UnboundedReceiver<String>>>,
+    config: Arc<Config>,
+) {
+    loop {
+        let next_id = receiver.lock().await.recv().await;
+
+        let id = match next_id {
+            Some(id) => id,
+            None => break,
+        };
+
+        let build = update_build(&builds, &id, |build| {
+            build.status = Status::Running;
+            build.started_at = Some(Utc::now());
+        });
+
+        let (mut build, log) = match build {
+            Some(job) => job,
+            None => continue,
+        };
+
+        let result = match history::start_build(&mut build) {
+            Ok(()) => {
+                update_build(&builds, &id, |entry| entry.number = build.number);
+
+                run_build(&build, &config, &log).await
+            }
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = &result {
+            log.push(&format!("Build failed: {}", err));
+        }
+
+        // The final status is set before closing the log so that streams ending on close see it.
+        let build = update_build(&builds, &id, |build| {
+            build.finished_at = Some(Utc::now());
+
+            match result {
+                Ok(built_image) => {
+                    build.status = Status::Pushed;
+                    build.image = Some(built_image.image);
+                    build.digest = built_image.digest;
+                    build.maps = built_image.maps;
+                }
+                Err(err) => {
+                    build.status = Status::Failed;
+                    build.error = Some(err.to_string());
+                }
+            }
+        });
+
+        let mut saved = false;
+
+        if let Some((build, _)) = build {
+            match history::save_build(&build) {
+                Ok(()) => saved = true,
+                Err(err) => log.push(&format!("Failed to save the build history: {}", err)),
+            }
+        }
+
+        log.finish();
+
+        // Finished builds are served from the history and their log file from now on, only the
+        // ones that couldn't be saved stay in memory.
+        if saved {
+            builds.lock().unwrap().remove(&id);
+        }
+    }
+}
+
+async fn run_build(build: &Build, config: &Config, log: &BuildLog) -> Result<BuiltImage, Error> {
+    let template = templates::snapshots::get_template_obj(&build.template, build.version)?;
+
+    templates::utils::build_template_dockerfile(&template, build, config, log).await
+}
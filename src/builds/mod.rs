@@ -0,0 +1,5 @@
+pub mod build;
+pub mod history;
+pub mod log;
+pub mod manager;
+pub mod routes;
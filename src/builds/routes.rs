@@ -0,0 +1,90 @@
+use rocket::http::Status;
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::serde_json::json;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::State;
+
+use crate::auth::guards::ReadOnly;
+use crate::responses::api_error::ApiError;
+use crate::responses::api_success::ApiSuccess;
+
+use super::{log, manager::BuildQueue};
+
+#[get("/<id>")]
+pub async fn get_build(
+    _role: ReadOnly,
+    id: String,
+    queue: &State<BuildQueue>,
+) -> Result<ApiSuccess, ApiError> {
+    let build = queue
+        .get(&id)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?
+        .ok_or_else(|| ApiError::new("The build doesn't exist.", Status::NotFound))?;
+
+    Ok(ApiSuccess::data(json!(build)))
+}
+
+#[get("/<id>/logs")]
+pub async fn get_build_logs(
+    _role: ReadOnly,
+    id: String,
+    queue: &State<BuildQueue>,
+) -> Result<EventStream![], ApiError> {
+    let build = queue
+        .get(&id)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?
+        .ok_or_else(|| ApiError::new("The build doesn't exist.", Status::NotFound))?;
+
+    // Finished builds are no longer in the queue, their log is only on disk.
+    let build_log = queue.get_log(&id);
+
+    let (lines, receiver) = match &build_log {
+        Some(build_log) => build_log.subscribe(),
+        None => {
+            let log_file_path_str = log::get_build_log_file_path(&build.template, &id);
+            let content = std::fs::read_to_string(log_file_path_str)
+                .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+            (content.lines().map(String::from).collect(), None)
+        }
+    };
+
+    let queue = queue.inner().clone();
+
+    Ok(EventStream! {
+        let mut sent = lines.len();
+        let mut receiver = receiver;
+
+        for line in lines {
+            yield Event::data(line);
+        }
+
+        while let Some(current_receiver) = receiver.as_mut() {
+            match current_receiver.recv().await {
+                Ok(line) => {
+                    sent += 1;
+                    yield Event::data(line);
+                }
+                // The receiver fell behind, the missed lines are taken back from the log.
+                Err(RecvError::Lagged(_)) => {
+                    let (missed_lines, new_receiver) = build_log
+                        .as_ref()
+                        .map(|build_log| build_log.subscribe_from(sent))
+                        .unwrap_or_default();
+
+                    sent += missed_lines.len();
+                    receiver = new_receiver;
+
+                    for line in missed_lines {
+                        yield Event::data(line);
+                    }
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+
+        if let Ok(Some(build)) = queue.get(&id) {
+            yield Event::json(&build).event("status");
+        }
+    })
+}
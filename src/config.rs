@@ -1,9 +1,21 @@
+use std::collections::HashMap;
+
+use crate::auth::role::Role;
+
+#[derive(Clone)]
 pub struct Config {
     pub registry_username: String,
     pub registry_password: String,
     pub registry_host: String,
 
     pub api_host: String,
+
+    pub build_workers: usize,
+
+    // Deleted maps are kept in the trash this long, no trash at all when 0.
+    pub map_trash_retention_days: u32,
+
+    pub tokens: HashMap<String, Role>,
 }
 
 impl Config {
@@ -12,7 +24,9 @@ impl Config {
         default_registry_password: &str,
         default_registry_host: &str,
         default_api_host: &str,
-    ) -> Config {
+        default_build_workers: usize,
+        default_map_trash_retention_days: u32,
+    ) -> Result<Config, String> {
         let registry_username = std::env::var("REGISTRY_USERNAME")
             .unwrap_or_else(|_| default_registry_username.to_string());
         let registry_password = std::env::var("REGISTRY_PASSWORD")
@@ -20,12 +34,53 @@ impl Config {
         let registry_host =
             std::env::var("REGISTRY_HOST").unwrap_or_else(|_| default_registry_host.to_string());
         let api_host = std::env::var("API_HOST").unwrap_or_else(|_| default_api_host.to_string());
+        let build_workers = std::env::var("BUILD_WORKERS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default_build_workers);
+        let map_trash_retention_days = std::env::var("MAP_TRASH_RETENTION_DAYS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default_map_trash_retention_days);
+        let tokens = match std::env::var("API_TOKENS") {
+            Ok(value) => parse_tokens(&value)?,
+            Err(_) => HashMap::new(),
+        };
 
-        Config {
+        Ok(Config {
             registry_username,
             registry_password,
             registry_host,
             api_host,
-        }
+            build_workers,
+            map_trash_retention_days,
+            tokens,
+        })
     }
 }
+
+// Tokens are given as a comma separated list of `<token>:<role>` entries.
+fn parse_tokens(value: &str) -> Result<HashMap<String, Role>, String> {
+    value
+        .split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (token, role) = entry.rsplit_once(':').ok_or_else(|| {
+                String::from("Invalid API_TOKENS entry, expected <token>:<role>.")
+            })?;
+
+            if token.is_empty() {
+                return Err(String::from(
+                    "Invalid API_TOKENS entry, the token is empty.",
+                ));
+            }
+
+            let role = role
+                .parse()
+                .map_err(|err| format!("Invalid API_TOKENS entry: {}.", err))?;
+
+            Ok((token.to_string(), role))
+        })
+        .collect()
+}
@@ -0,0 +1,133 @@
+use std::fs::File;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket::serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::responses::api_error::ApiError;
+use crate::safe_path;
+use crate::templates::utils;
+
+#[derive(Serialize)]
+pub struct FileEntry {
+    pub path: String,
+    pub directory: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    pub modified: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+// Lists every file and directory under the given one, with paths relative to it. Metadata with the
+// reserved extension can't be downloaded and isn't listed.
+pub fn get_tree(dir_path: &str) -> Result<Vec<FileEntry>, Error> {
+    let mut entries = Vec::new();
+    let paths = glob::glob(&format!("{}/**/*", dir_path)).map_err(Error::other)?;
+
+    for glob_result in paths {
+        let path = glob_result.map_err(Error::other)?;
+        let relative_path = utils::strip_base_path(&path, dir_path)?;
+
+        if safe_path::is_reserved(relative_path) {
+            continue;
+        }
+
+        let metadata = std::fs::metadata(&path)?;
+        let directory = metadata.is_dir();
+
+        entries.push(FileEntry {
+            path: relative_path.to_string_lossy().to_string(),
+            directory,
+            size: (!directory).then_some(metadata.len()),
+            modified: DateTime::from(metadata.modified()?),
+            sha256: if directory {
+                None
+            } else {
+                Some(hash_file(&path)?)
+            },
+        });
+    }
+
+    Ok(entries)
+}
+
+pub fn hash_file(path: &Path) -> Result<String, Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+
+    std::io::copy(&mut file, &mut hasher)?;
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+// The file routes of parents and templates share these, the base path being the directory of the
+// parent or the template.
+
+pub fn list_files(base_path: &str) -> Result<Vec<FileEntry>, ApiError> {
+    get_tree(base_path).map_err(|err| ApiError::default(err.to_string().as_str()))
+}
+
+pub fn open_file(base_path: &str, path: &str) -> Result<File, ApiError> {
+    let file_path = get_existing_file_path(base_path, path)?;
+
+    File::open(file_path).map_err(|err| ApiError::default(err.to_string().as_str()))
+}
+
+// Returns where an upload to the given relative path goes, creating the missing directories.
+pub fn prepare_upload_path(base_path: &str, path: &str) -> Result<PathBuf, ApiError> {
+    let file_path = safe_path::join(base_path, path)?;
+
+    if file_path.is_dir() {
+        return Err(ApiError::new(
+            "The destination is a directory.",
+            Status::Conflict,
+        ));
+    }
+
+    if let Some(file_dir) = file_path.parent() {
+        std::fs::create_dir_all(file_dir)
+            .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    }
+
+    Ok(file_path)
+}
+
+pub fn rename_file(base_path: &str, path: &str, to: &str) -> Result<(), ApiError> {
+    let file_path = get_existing_file_path(base_path, path)?;
+    let new_file_path = safe_path::join(base_path, to)?;
+
+    if new_file_path.exists() {
+        return Err(ApiError::new(
+            "The destination already exists.",
+            Status::Conflict,
+        ));
+    }
+
+    if let Some(new_file_dir) = new_file_path.parent() {
+        std::fs::create_dir_all(new_file_dir)
+            .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    }
+
+    std::fs::rename(file_path, new_file_path)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))
+}
+
+pub fn delete_file(base_path: &str, path: &str) -> Result<(), ApiError> {
+    let file_path = get_existing_file_path(base_path, path)?;
+
+    std::fs::remove_file(file_path).map_err(|err| ApiError::default(err.to_string().as_str()))
+}
+
+fn get_existing_file_path(base_path: &str, path: &str) -> Result<PathBuf, ApiError> {
+    let file_path = safe_path::join(base_path, path)?;
+
+    if !file_path.is_file() {
+        return Err(ApiError::new("The file doesn't exist.", Status::NotFound));
+    }
+
+    Ok(file_path)
+}
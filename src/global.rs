@@ -3,5 +3,8 @@
 pub const PARENTS_DIR: &str = "./data/parents";
 pub const TEMPLATES_DIR: &str = "./data/templates";
 pub const DATA_TMP_FILES_DIR: &str = "./data/tmp";
-pub const TMP_DIR: &str = "./tmp";
 pub const MAPS_DIR: &str = "./data/maps";
+pub const MAPS_TRASH_DIR: &str = "./data/trash/maps";
+pub const SNAPSHOTS_DIR: &str = "./data/snapshots";
+pub const CACHE_DIR: &str = "./data/cache";
+pub const PLUGINS_DIR: &str = "./data/plugins";
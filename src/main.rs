@@ -5,20 +5,30 @@ use rocket::data::{Limits, ToByteUnit};
 use rocket::http::Status;
 use rocket::{routes, Request};
 
+use crate::builds::manager::BuildQueue;
 use crate::config::Config;
 use crate::responses::api_error::ApiError;
 
+mod auth;
+mod builds;
 mod config;
+mod files;
 mod global;
 mod maps;
 mod parents;
+mod plugins;
 mod responses;
+mod safe_path;
 mod templates;
 
 fn init_base_dirs() -> std::io::Result<()> {
     std::fs::create_dir_all(global::PARENTS_DIR)?;
     std::fs::create_dir_all(global::MAPS_DIR)?;
+    std::fs::create_dir_all(global::MAPS_TRASH_DIR)?;
     std::fs::create_dir_all(global::DATA_TMP_FILES_DIR)?;
+    std::fs::create_dir_all(global::SNAPSHOTS_DIR)?;
+    std::fs::create_dir_all(global::CACHE_DIR)?;
+    std::fs::create_dir_all(global::PLUGINS_DIR)?;
     std::fs::create_dir_all(global::TEMPLATES_DIR)
 }
 
@@ -32,14 +42,21 @@ fn default_catcher(status: Status, _request: &Request) -> ApiError {
     ApiError::new("An error is occurred with the server.", status)
 }
 
-#[launch]
-fn rocket() -> _ {
-    init_base_dirs().expect("Failed to create base directories");
-
-    let config = Config::new("admin", "admin", "localhost:5000", "localhost:8000");
+#[catch(401)]
+fn unauthorized_catcher() -> ApiError {
+    ApiError::new("A valid API token is required.", Status::Unauthorized)
+}
 
-    std::env::set_var("TMPDIR", global::DATA_TMP_FILES_DIR);
+#[catch(403)]
+fn forbidden_catcher() -> ApiError {
+    ApiError::new(
+        "The API token doesn't allow this action.",
+        Status::Forbidden,
+    )
+}
 
+#[launch]
+async fn rocket() -> _ {
     let limits = Limits::default()
         .limit("file", 100.megabytes())
         .limit("data-form", 100.megabytes());
@@ -49,9 +66,34 @@ fn rocket() -> _ {
         .merge(("limits", limits))
         .merge(("log_level", "debug"));
 
-    rocket::custom(rocket_config)
-        .register("/", catchers![default_catcher])
+    // Created first as it sets up the logger, the migrations below report what they skip.
+    let rocket = rocket::custom(rocket_config);
+
+    init_base_dirs().expect("Failed to create base directories");
+
+    let config = match Config::new("admin", "admin", "localhost:5000", "localhost", 2, 7) {
+        Ok(config) => config,
+        Err(err) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let build_queue = BuildQueue::new(&config).expect("Failed to start the build queue");
+
+    maps::manager::migrate_legacy_maps().expect("Failed to migrate the maps");
+    maps::trash::purge_expired(config.map_trash_retention_days)
+        .expect("Failed to purge the map trash");
+
+    std::env::set_var("TMPDIR", global::DATA_TMP_FILES_DIR);
+
+    rocket
+        .register(
+            "/",
+            catchers![default_catcher, unauthorized_catcher, forbidden_catcher],
+        )
         .manage(config)
+        .manage(build_queue)
         .mount("/", routes![ping])
         .mount(
             "/parents",
@@ -60,8 +102,19 @@ fn rocket() -> _ {
                 parents::routes::get_parents,
                 parents::routes::create,
                 parents::routes::delete,
+                parents::routes::update,
                 parents::routes::push_plugin,
-                parents::routes::push_file
+                parents::routes::get_plugins,
+                parents::routes::delete_plugin,
+                parents::routes::replace_plugin,
+                parents::routes::push_file,
+                parents::routes::push_nested_file,
+                parents::routes::get_files,
+                parents::routes::download_file,
+                parents::routes::rename_file,
+                parents::routes::delete_file,
+                parents::routes::push_dockerfile,
+                parents::routes::delete_dockerfile
             ],
         )
         .mount(
@@ -73,17 +126,41 @@ fn rocket() -> _ {
                 templates::routes::delete,
                 templates::routes::update,
                 templates::routes::push_plugin,
+                templates::routes::get_plugins,
+                templates::routes::delete_plugin,
+                templates::routes::replace_plugin,
+                templates::routes::validate,
                 templates::routes::push_file,
+                templates::routes::push_nested_file,
+                templates::routes::get_files,
+                templates::routes::get_merged_files,
+                templates::routes::download_file,
+                templates::routes::rename_file,
+                templates::routes::delete_file,
                 templates::routes::to_zip,
-                templates::routes::build
+                templates::routes::build,
+                templates::routes::get_builds,
+                templates::routes::get_snapshots,
+                templates::routes::diff_snapshots,
+                templates::routes::rollback
             ],
         )
+        .mount(
+            "/builds",
+            routes![builds::routes::get_build, builds::routes::get_build_logs],
+        )
         .mount(
             "/maps",
             routes![
                 maps::routes::delete,
+                maps::routes::delete_version,
+                maps::routes::restore,
+                maps::routes::get_trash,
                 maps::routes::push_map,
+                maps::routes::optimize,
                 maps::routes::get_map,
+                maps::routes::get_map_details,
+                maps::routes::update_details,
                 maps::routes::get_maps
             ],
         )
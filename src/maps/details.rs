@@ -0,0 +1,86 @@
+use rocket::serde::{Deserialize, Serialize};
+
+// What the game designers say about a map, shared by all of its versions.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct MapDetails {
+    pub display_name: Option<String>,
+    pub authors: Vec<String>,
+    pub modes: Vec<String>,
+    pub min_players: Option<u16>,
+    pub max_players: Option<u16>,
+    pub tags: Vec<String>,
+    // Chunks outside of it are dropped when the map is optimized.
+    pub bounds: Option<Bounds>,
+}
+
+// Block coordinates, both corners included.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Bounds {
+    pub min_x: i32,
+    pub min_z: i32,
+    pub max_x: i32,
+    pub max_z: i32,
+}
+
+impl MapDetails {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if let (Some(min_players), Some(max_players)) = (self.min_players, self.max_players) {
+            if min_players > max_players {
+                return Err("The minimum players exceed the maximum players.");
+            }
+        }
+
+        if let Some(bounds) = &self.bounds {
+            if bounds.min_x > bounds.max_x || bounds.min_z > bounds.max_z {
+                return Err("The minimum bounds exceed the maximum bounds.");
+            }
+        }
+
+        let values = self.authors.iter().chain(&self.modes).chain(&self.tags);
+
+        for value in values.chain(&self.display_name) {
+            if value.trim().is_empty() {
+                return Err("Names, authors, modes and tags can't be empty.");
+            }
+        }
+
+        Ok(())
+    }
+
+    // Modes and tags are compared case-insensitively, so they are stored lowercase once.
+    pub fn normalize(&mut self) {
+        for values in [&mut self.modes, &mut self.tags] {
+            for value in values.iter_mut() {
+                *value = value.trim().to_lowercase();
+            }
+
+            values.sort();
+            values.dedup();
+        }
+    }
+
+    // A map matches when it supports the mode and carries every tag.
+    pub fn matches(&self, mode: Option<&str>, tags: &[String]) -> bool {
+        let has = |values: &[String], value: &str| {
+            values
+                .iter()
+                .any(|found| found == &value.trim().to_lowercase())
+        };
+
+        mode.is_none_or(|mode| has(&self.modes, mode))
+            && tags.iter().all(|tag| has(&self.tags, tag))
+    }
+}
+
+impl Bounds {
+    // A chunk is kept as soon as one of its columns is inside.
+    pub fn contains_chunk(&self, chunk_x: i64, chunk_z: i64) -> bool {
+        let (min_x, min_z) = (chunk_x * 16, chunk_z * 16);
+
+        min_x + 15 >= i64::from(self.min_x)
+            && min_x <= i64::from(self.max_x)
+            && min_z + 15 >= i64::from(self.min_z)
+            && min_z <= i64::from(self.max_z)
+    }
+}
@@ -1,14 +1,468 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind};
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use rocket::fs::TempFile;
+use rocket::http::Status;
+use rocket::serde::json::serde_json;
+use rocket::serde::Serialize;
+use rocket::tokio;
 
 use crate::global;
+use crate::responses::api_error::ApiError;
+
+use super::details::MapDetails;
+use super::metadata::{self, WorldMetadata};
+use super::optimize::{self, OptimizationReport};
+use super::reference::{MapReference, ResolvedMap};
+
+// Held while a map is checked and deleted, and while a template referencing maps is checked and
+// saved, so that a map can't be deleted while a template starting to use it is being written.
+// Pushes hold it too, so that two of them never get the same version.
+static MAPS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize)]
+pub struct Map {
+    pub name: String,
+    pub latest_version: u32,
+    #[serde(flatten)]
+    pub details: MapDetails,
+    pub versions: Vec<MapVersion>,
+}
+
+#[derive(Serialize)]
+pub struct MapSummary {
+    pub name: String,
+    pub latest_version: u32,
+    #[serde(flatten)]
+    pub details: MapDetails,
+}
 
+#[derive(Serialize)]
+pub struct MapVersion {
+    pub version: u32,
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+    // Only missing for maps pushed before uploads were checked that don't hold a readable world.
+    pub world: Option<WorldMetadata>,
+}
+
+pub fn lock_maps() -> MutexGuard<'static, ()> {
+    MAPS_LOCK.lock().unwrap()
+}
+
+// Every push is kept as a version of its own, a version is never modified once written.
 pub fn get_map_path(name: &str) -> String {
-    format!("{}/{}.zip", global::MAPS_DIR, name)
+    format!("{}/{}", global::MAPS_DIR, name)
+}
+
+pub fn get_version_path(name: &str, version: u32) -> String {
+    format!("{}/{}.zip", get_map_path(name), version)
+}
+
+pub fn get_metadata_file_path(name: &str, version: u32) -> String {
+    format!("{}/{}.epsilon", get_map_path(name), version)
+}
+
+pub fn get_details_file_path(name: &str) -> String {
+    format!("{}/details.epsilon", get_map_path(name))
 }
 
 pub fn map_exist(name: &str) -> bool {
-    let map_path_str = &get_map_path(name);
-    let map_path = Path::new(map_path_str);
+    get_versions(name).is_ok_and(|versions| !versions.is_empty())
+}
+
+pub fn get_map_names() -> Result<Vec<String>, Error> {
+    let mut maps = Vec::new();
+
+    let map_directories = std::fs::read_dir(global::MAPS_DIR)?
+        .filter_map(|dir| dir.ok())
+        .filter(|dir| dir.path().is_dir());
+
+    for dir in map_directories {
+        let name = dir.file_name().to_string_lossy().to_string();
+
+        if map_exist(&name) {
+            maps.push(name);
+        }
+    }
+
+    maps.sort();
+
+    Ok(maps)
+}
+
+// Returns the versions of a map, oldest first.
+pub fn get_versions(name: &str) -> Result<Vec<u32>, Error> {
+    let map_path_str = get_map_path(name);
+
+    if !Path::new(&map_path_str).is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut versions: Vec<u32> = std::fs::read_dir(map_path_str)?
+        .filter_map(|file| file.ok())
+        .map(|file| file.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "zip"))
+        .filter_map(|path| path.file_stem()?.to_str()?.parse().ok())
+        .collect();
+
+    versions.sort();
+
+    Ok(versions)
+}
+
+pub fn resolve(reference: &MapReference) -> Result<ResolvedMap, Error> {
+    let versions = get_versions(&reference.name)?;
+
+    let version = match reference.version {
+        Some(version) => versions.into_iter().find(|found| *found == version),
+        None => versions.last().copied(),
+    };
+
+    match version {
+        Some(version) => Ok(ResolvedMap {
+            name: reference.name.clone(),
+            version,
+        }),
+        None => Err(Error::new(
+            ErrorKind::NotFound,
+            match reference.version {
+                Some(version) => format!("The map {} has no version {}.", reference.name, version),
+                None => format!("The map {} doesn't exist.", reference.name),
+            },
+        )),
+    }
+}
+
+// The references of a template are resolved once, so an archive can't mix the versions pushed
+// while it is being written.
+pub fn resolve_all(references: &[String]) -> Result<Vec<ResolvedMap>, Error> {
+    references
+        .iter()
+        .map(|reference| {
+            let reference = reference
+                .parse()
+                .map_err(|err| Error::new(ErrorKind::InvalidInput, err))?;
+
+            resolve(&reference)
+        })
+        .collect()
+}
+
+// Maps that don't match the mode and every tag are left out.
+pub fn get_maps(mode: Option<&str>, tags: &[String]) -> Result<Vec<MapSummary>, Error> {
+    let mut maps = Vec::new();
+
+    for name in get_map_names()? {
+        let details = get_details(&name)?;
+
+        if details.matches(mode, tags) {
+            maps.push(MapSummary {
+                latest_version: get_versions(&name)?.last().copied().unwrap_or(0),
+                name,
+                details,
+            });
+        }
+    }
+
+    Ok(maps)
+}
+
+pub fn get_map(name: &str) -> Result<Map, Error> {
+    let mut versions = Vec::new();
+
+    for version in get_versions(name)? {
+        versions.push(get_map_version(name, version)?);
+    }
+
+    Ok(Map {
+        name: name.to_string(),
+        latest_version: versions.last().map(|version| version.version).unwrap_or(0),
+        details: get_details(name)?,
+        versions,
+    })
+}
+
+// Maps never described have empty details.
+pub fn get_details(name: &str) -> Result<MapDetails, Error> {
+    let details_file_path_str = get_details_file_path(name);
+
+    if !Path::new(&details_file_path_str).exists() {
+        return Ok(MapDetails::default());
+    }
+
+    let file = File::open(details_file_path_str)?;
+
+    Ok(serde_json::from_reader(&file)?)
+}
+
+pub fn save_details(name: &str, details: &MapDetails) -> Result<(), Error> {
+    let details_file_path_str = get_details_file_path(name);
+    let tmp_file_path_str = format!("{}.{}.tmp", details_file_path_str, uuid::Uuid::new_v4());
+    let tmp_file = File::create(&tmp_file_path_str)?;
+
+    serde_json::to_writer_pretty(tmp_file, details)?;
+
+    std::fs::rename(tmp_file_path_str, details_file_path_str)
+}
+
+pub fn get_map_version(name: &str, version: u32) -> Result<MapVersion, Error> {
+    let metadata = std::fs::metadata(get_version_path(name, version))?;
+
+    Ok(MapVersion {
+        version,
+        size: metadata.len(),
+        created_at: DateTime::from(metadata.modified()?),
+        world: get_metadata(name, version)?,
+    })
+}
+
+// Versions without stored metadata are read once, invalid ones have none.
+fn get_metadata(name: &str, version: u32) -> Result<Option<WorldMetadata>, Error> {
+    let metadata_file_path_str = get_metadata_file_path(name, version);
+
+    if Path::new(&metadata_file_path_str).exists() {
+        let file = File::open(metadata_file_path_str)?;
+
+        return Ok(Some(serde_json::from_reader(&file)?));
+    }
+
+    match metadata::read_metadata(Path::new(&get_version_path(name, version))) {
+        Ok(metadata) => {
+            save_metadata(name, version, &metadata)?;
+            Ok(Some(metadata))
+        }
+        Err(err) if err.kind() == ErrorKind::InvalidData => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn save_metadata(name: &str, version: u32, metadata: &WorldMetadata) -> Result<(), Error> {
+    let metadata_file_path_str = get_metadata_file_path(name, version);
+    let tmp_file_path_str = format!("{}.{}.tmp", metadata_file_path_str, uuid::Uuid::new_v4());
+    let tmp_file = File::create(&tmp_file_path_str)?;
+
+    serde_json::to_writer_pretty(tmp_file, metadata)?;
+
+    std::fs::rename(tmp_file_path_str, metadata_file_path_str)
+}
+
+// The upload is fully written next to the other temporary files first and checked to hold a
+// world, then renamed into place as the next version, so a version is only ever a readable map.
+// An optimized upload is checked before being optimized into another temporary file.
+pub async fn install(
+    file: &mut TempFile<'_>,
+    name: &str,
+    optimize: bool,
+) -> Result<(MapVersion, Option<OptimizationReport>), ApiError> {
+    let tmp_file_path_str = get_tmp_file_path();
+
+    file.persist_to(&tmp_file_path_str)
+        .await
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    let (tmp_file_path_str, report) = match optimize {
+        true => {
+            let result = match check(Path::new(&tmp_file_path_str)) {
+                Ok(_) => optimize_to_tmp_file(name, tmp_file_path_str.clone()).await,
+                Err(err) => Err(err),
+            };
+
+            let _ = std::fs::remove_file(&tmp_file_path_str);
+            let (optimized_file_path_str, report) = result?;
+
+            (optimized_file_path_str, Some(report))
+        }
+        false => (tmp_file_path_str, None),
+    };
+
+    let result = check_and_move(Path::new(&tmp_file_path_str), name, false);
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_file_path_str);
+    }
+
+    Ok((result?, report))
+}
+
+// Versions never change, the optimized world becomes the next version of the map.
+pub async fn optimize_version(
+    name: &str,
+    version: u32,
+) -> Result<(MapVersion, OptimizationReport), ApiError> {
+    let (tmp_file_path_str, report) =
+        optimize_to_tmp_file(name, get_version_path(name, version)).await?;
+
+    let result = check_and_move(Path::new(&tmp_file_path_str), name, true);
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_file_path_str);
+    }
+
+    Ok((result?, report))
+}
+
+// Worlds can be large, they are optimized on the blocking pool, with the bounds of the map. A world
+// that can't be optimized is reported like one that fails its check.
+async fn optimize_to_tmp_file(
+    name: &str,
+    source: String,
+) -> Result<(String, OptimizationReport), ApiError> {
+    optimize_world(name, source)
+        .await
+        .map_err(|err| match err.kind() {
+            ErrorKind::InvalidData => ApiError::new(&err.to_string(), Status::BadRequest),
+            _ => ApiError::default(err.to_string().as_str()),
+        })
+}
+
+async fn optimize_world(name: &str, source: String) -> Result<(String, OptimizationReport), Error> {
+    let bounds = get_details(name)?.bounds;
+    let tmp_file_path_str = get_tmp_file_path();
+    let destination = tmp_file_path_str.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        optimize::optimize(Path::new(&source), Path::new(&destination), bounds.as_ref())
+    })
+    .await
+    .map_err(Error::other)?;
+
+    match result {
+        Ok(report) => Ok((tmp_file_path_str, report)),
+        Err(err) => {
+            let _ = std::fs::remove_file(&tmp_file_path_str);
+            Err(err)
+        }
+    }
+}
+
+fn get_tmp_file_path() -> String {
+    format!(
+        "{}/{}.tmp",
+        global::DATA_TMP_FILES_DIR,
+        uuid::Uuid::new_v4()
+    )
+}
+
+fn check(map_path: &Path) -> Result<WorldMetadata, ApiError> {
+    metadata::read_metadata(map_path).map_err(|err| match err.kind() {
+        ErrorKind::InvalidData => ApiError::new(&err.to_string(), Status::BadRequest),
+        _ => ApiError::default(err.to_string().as_str()),
+    })
+}
+
+// A version made from an existing one is only added if its map is still there, it could have been
+// deleted or trashed while the world was optimized.
+fn check_and_move(
+    tmp_file_path: &Path,
+    name: &str,
+    existing_map: bool,
+) -> Result<MapVersion, ApiError> {
+    let metadata = check(tmp_file_path)?;
+
+    let _maps_guard = lock_maps();
+
+    if existing_map && !map_exist(name) {
+        return Err(ApiError::new("The map doesn't exist.", Status::NotFound));
+    }
+
+    let version = get_versions(name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?
+        .last()
+        .map_or(1, |latest| latest + 1);
+
+    std::fs::create_dir_all(get_map_path(name))
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    // The metadata goes first, a version is only listed once its zip is there.
+    save_metadata(name, version, &metadata)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    std::fs::rename(tmp_file_path, get_version_path(name, version))
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    get_map_version(name, version).map_err(|err| ApiError::default(err.to_string().as_str()))
+}
+
+// Maps used to be a single <name>.zip, each one becomes the first version of its map. The entries
+// that can't be migrated are reported and left as they are.
+pub fn migrate_legacy_maps() -> Result<(), Error> {
+    for file in std::fs::read_dir(global::MAPS_DIR)? {
+        let path = match file {
+            Ok(file) => file.path(),
+            Err(err) => {
+                error!("Failed to read an entry of the maps directory: {}", err);
+                continue;
+            }
+        };
+
+        if !path.is_file() {
+            continue;
+        }
+
+        if let Err(err) = migrate_legacy_map(&path) {
+            error!(
+                "Failed to migrate the legacy map {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn migrate_legacy_map(path: &Path) -> Result<(), Error> {
+    if path.extension().is_none_or(|ext| ext != "zip") {
+        return Ok(());
+    }
+
+    let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some(name) => name.to_string(),
+        None => return Ok(()),
+    };
+
+    let version_path_str = get_version_path(&name, 1);
+
+    if Path::new(&version_path_str).exists() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("The map {} already has a first version.", name),
+        ));
+    }
+
+    std::fs::create_dir_all(get_map_path(&name))?;
+    std::fs::rename(path, version_path_str)
+}
+
+// The latest version can't be deleted, the next push would otherwise reuse its number.
+pub fn delete_version(name: &str, version: u32) -> Result<(), ApiError> {
+    let versions = get_versions(name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    if !versions.contains(&version) {
+        return Err(ApiError::new(
+            "The map version doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    if versions.last() == Some(&version) {
+        return Err(ApiError::new(
+            "The latest version of a map can't be deleted.",
+            Status::Conflict,
+        ));
+    }
+
+    std::fs::remove_file(get_version_path(name, version))
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    map_path.exists()
+    match std::fs::remove_file(get_metadata_file_path(name, version)) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(ApiError::default(err.to_string().as_str()))
+        }
+        _ => Ok(()),
+    }
 }
@@ -0,0 +1,354 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use rocket::serde::{Deserialize, Serialize};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use super::nbt::{self, Tag};
+
+#[derive(Serialize, Deserialize)]
+pub struct WorldMetadata {
+    pub level_name: Option<String>,
+    pub data_version: Option<i64>,
+    pub minecraft_version: Option<String>,
+    pub spawn: Option<Spawn>,
+    pub game_rules: BTreeMap<String, String>,
+    pub dimensions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Spawn {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+// The map is extracted as the world folder, so its level.dat has to be at the root of the zip.
+pub fn read_metadata(map_path: &Path) -> Result<WorldMetadata, Error> {
+    let map_file = File::open(map_path)?;
+    let mut map_archive = ZipArchive::new(map_file)
+        .map_err(|_| invalid("The map isn't a valid zip archive.".to_string()))?;
+
+    let dimensions = get_dimensions(map_archive.file_names());
+
+    let level_dat = match map_archive.by_name("level.dat") {
+        Ok(level_dat) => level_dat,
+        Err(ZipError::FileNotFound) => {
+            return Err(invalid(
+                "The map doesn't contain a level.dat at its root.".to_string(),
+            ))
+        }
+        Err(err) => return Err(invalid(format!("The map can't be read: {}", err))),
+    };
+
+    let root = nbt::read_gzip(level_dat)
+        .map_err(|err| invalid(format!("The level.dat can't be read: {}", err)))?;
+    let data = root
+        .get("Data")
+        .ok_or_else(|| invalid("The level.dat has no Data compound.".to_string()))?;
+
+    Ok(WorldMetadata {
+        level_name: data
+            .get("LevelName")
+            .and_then(Tag::as_str)
+            .map(String::from),
+        data_version: data.get("DataVersion").and_then(Tag::as_i64),
+        minecraft_version: data
+            .get("Version")
+            .and_then(|version| version.get("Name"))
+            .and_then(Tag::as_str)
+            .map(String::from),
+        spawn: get_spawn(data),
+        game_rules: get_game_rules(data),
+        dimensions,
+    })
+}
+
+// Worlds saved since 1.21.5 keep the spawn in a compound, older ones in three fields.
+fn get_spawn(data: &Tag) -> Option<Spawn> {
+    if let Some(pos) = data
+        .get("spawn")
+        .and_then(|spawn| spawn.get("pos"))
+        .and_then(Tag::as_i64_vec)
+    {
+        return match pos[..] {
+            [x, y, z] => Some(Spawn { x, y, z }),
+            _ => None,
+        };
+    }
+
+    Some(Spawn {
+        x: data.get("SpawnX")?.as_i64()?,
+        y: data.get("SpawnY")?.as_i64()?,
+        z: data.get("SpawnZ")?.as_i64()?,
+    })
+}
+
+// Game rules used to all be strings, newer versions store them typed.
+fn get_game_rules(data: &Tag) -> BTreeMap<String, String> {
+    let mut game_rules = BTreeMap::new();
+    let game_rules_tag = match data.get("GameRules").or_else(|| data.get("game_rules")) {
+        Some(game_rules_tag) => game_rules_tag,
+        None => return game_rules,
+    };
+
+    for key in game_rules_tag.keys() {
+        let value = match game_rules_tag.get(key) {
+            Some(Tag::String(value)) => value.clone(),
+            Some(Tag::Byte(value)) => (*value != 0).to_string(),
+            Some(tag) => match tag.as_i64() {
+                Some(value) => value.to_string(),
+                None => match tag.as_f64() {
+                    Some(value) => value.to_string(),
+                    None => continue,
+                },
+            },
+            None => continue,
+        };
+
+        game_rules.insert(key.clone(), value);
+    }
+
+    game_rules
+}
+
+// A dimension is there when its region folder is, custom ones live under dimensions/<namespace>.
+fn get_dimensions<'a>(file_names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut dimensions = BTreeSet::new();
+
+    for file_name in file_names {
+        let parts: Vec<&str> = file_name.split('/').collect();
+
+        let dimension = match parts[..] {
+            ["region", ..] => "minecraft:overworld".to_string(),
+            ["DIM-1", "region", ..] => "minecraft:the_nether".to_string(),
+            ["DIM1", "region", ..] => "minecraft:the_end".to_string(),
+            ["dimensions", namespace, ref rest @ ..] => {
+                match rest.iter().position(|part| *part == "region") {
+                    Some(index) if index > 0 => {
+                        format!("{}:{}", namespace, rest[..index].join("/"))
+                    }
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+
+        dimensions.insert(dimension);
+    }
+
+    dimensions.into_iter().collect()
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    use super::*;
+
+    fn compound(items: Vec<(&str, Tag)>) -> Tag {
+        Tag::Compound(
+            items
+                .into_iter()
+                .map(|(key, tag)| (key.to_string(), tag))
+                .collect::<HashMap<String, Tag>>(),
+        )
+    }
+
+    fn write_map(files: &[(&str, &[u8])]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}.zip", uuid::Uuid::new_v4()));
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+
+        for (name, content) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+
+        zip.finish().unwrap();
+
+        path
+    }
+
+    fn read_map(files: &[(&str, &[u8])]) -> Result<WorldMetadata, Error> {
+        let path = write_map(files);
+        let result = read_metadata(&path);
+
+        std::fs::remove_file(path).unwrap();
+
+        result
+    }
+
+    // { Data: { LevelName: "lobby", spawn: { pos: [I; 1, 64, -2] } } }
+    fn level_dat() -> Vec<u8> {
+        let mut nbt = vec![10, 0, 0];
+        nbt.extend([10, 0, 4]);
+        nbt.extend(b"Data");
+        nbt.extend([8, 0, 9]);
+        nbt.extend(b"LevelName");
+        nbt.extend([0, 5]);
+        nbt.extend(b"lobby");
+        nbt.extend([10, 0, 5]);
+        nbt.extend(b"spawn");
+        nbt.extend([11, 0, 3]);
+        nbt.extend(b"pos");
+        nbt.extend(3i32.to_be_bytes());
+
+        for value in [1i32, 64, -2] {
+            nbt.extend(value.to_be_bytes());
+        }
+
+        nbt.extend([0, 0, 0]);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&nbt).unwrap();
+
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn read_metadata_reads_the_root_level_dat() {
+        let level_dat = level_dat();
+        let metadata = read_map(&[
+            ("level.dat", &level_dat),
+            ("region/r.0.0.mca", b""),
+            ("DIM-1/region/r.0.0.mca", b""),
+        ])
+        .unwrap();
+
+        assert_eq!(metadata.level_name.as_deref(), Some("lobby"));
+        assert!(metadata
+            .spawn
+            .is_some_and(|spawn| (spawn.x, spawn.y, spawn.z) == (1, 64, -2)));
+        assert_eq!(
+            metadata.dimensions,
+            vec!["minecraft:overworld", "minecraft:the_nether"]
+        );
+    }
+
+    #[test]
+    fn read_metadata_rejects_invalid_maps() {
+        let level_dat = level_dat();
+        let truncated_level_dat = &level_dat[..level_dat.len() / 2];
+        let maps: [&[(&str, &[u8])]; 4] = [
+            &[("region/r.0.0.mca", b"")],
+            &[("world/level.dat", &level_dat)],
+            &[("level.dat", truncated_level_dat)],
+            &[("level.dat", b"not gzip")],
+        ];
+
+        for files in maps {
+            assert_eq!(
+                read_map(files).err().unwrap().kind(),
+                ErrorKind::InvalidData
+            );
+        }
+
+        let path = std::env::temp_dir().join(format!("{}.zip", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"not a zip").unwrap();
+
+        let result = read_metadata(&path);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(result.err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn get_spawn_reads_the_legacy_fields() {
+        let data = compound(vec![
+            ("SpawnX", Tag::Int(-10)),
+            ("SpawnY", Tag::Int(70)),
+            ("SpawnZ", Tag::Int(5)),
+        ]);
+
+        assert!(get_spawn(&data).is_some_and(|spawn| (spawn.x, spawn.y, spawn.z) == (-10, 70, 5)));
+    }
+
+    #[test]
+    fn get_spawn_reads_the_spawn_compound() {
+        let data = compound(vec![
+            (
+                "spawn",
+                compound(vec![("pos", Tag::IntArray(vec![3, 100, -4]))]),
+            ),
+            ("SpawnX", Tag::Int(0)),
+            ("SpawnY", Tag::Int(0)),
+            ("SpawnZ", Tag::Int(0)),
+        ]);
+
+        assert!(get_spawn(&data).is_some_and(|spawn| (spawn.x, spawn.y, spawn.z) == (3, 100, -4)));
+    }
+
+    #[test]
+    fn get_spawn_ignores_incomplete_spawns() {
+        let short_pos = compound(vec![(
+            "spawn",
+            compound(vec![("pos", Tag::IntArray(vec![3, 100]))]),
+        )]);
+        let missing_field = compound(vec![("SpawnX", Tag::Int(0)), ("SpawnY", Tag::Int(0))]);
+
+        assert!(get_spawn(&short_pos).is_none());
+        assert!(get_spawn(&missing_field).is_none());
+    }
+
+    #[test]
+    fn get_game_rules_reads_string_and_typed_rules() {
+        let legacy = compound(vec![(
+            "GameRules",
+            compound(vec![("doDaylightCycle", Tag::String("false".to_string()))]),
+        )]);
+        let typed = compound(vec![(
+            "game_rules",
+            compound(vec![
+                ("minecraft:advance_time", Tag::Byte(0)),
+                ("minecraft:spawn_radius", Tag::Int(10)),
+                ("unknown", Tag::List(Vec::new())),
+            ]),
+        )]);
+
+        assert_eq!(
+            get_game_rules(&legacy),
+            BTreeMap::from([("doDaylightCycle".to_string(), "false".to_string())])
+        );
+        assert_eq!(
+            get_game_rules(&typed),
+            BTreeMap::from([
+                ("minecraft:advance_time".to_string(), "false".to_string()),
+                ("minecraft:spawn_radius".to_string(), "10".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn get_dimensions_finds_region_folders() {
+        let file_names = [
+            "level.dat",
+            "region/r.0.0.mca",
+            "DIM1/region/r.0.0.mca",
+            "DIM-1/poi/r.0.0.mca",
+            "dimensions/custom/sky/islands/region/r.0.0.mca",
+            "dimensions/custom/region/r.0.0.mca",
+        ];
+
+        assert_eq!(
+            get_dimensions(file_names.into_iter()),
+            vec![
+                "custom:sky/islands",
+                "minecraft:overworld",
+                "minecraft:the_end"
+            ]
+        );
+    }
+}
@@ -1,2 +1,8 @@
+pub mod details;
 pub mod manager;
+pub mod metadata;
+pub mod nbt;
+pub mod optimize;
+pub mod reference;
 pub mod routes;
+pub mod trash;
@@ -0,0 +1,453 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read};
+
+use flate2::read::GzDecoder;
+
+// Only what is needed to read a level.dat or a chunk, a big endian compound.
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+// Nested deeper than any real level.dat, a crafted one can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+// Bigger than any real level.dat or chunk once decompressed. It bounds both what is read and the
+// memory the tags take, as a list claims its length before its items.
+pub const MAX_NBT_SIZE: u64 = 64 * 1024 * 1024;
+
+impl Tag {
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(compound) => compound.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn keys(&self) -> Vec<&String> {
+        match self {
+            Tag::Compound(compound) => compound.keys().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Tag::Byte(value) => Some((*value).into()),
+            Tag::Short(value) => Some((*value).into()),
+            Tag::Int(value) => Some((*value).into()),
+            Tag::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Tag::Float(value) => Some((*value).into()),
+            Tag::Double(value) => Some(*value),
+            _ => self.as_i64().map(|value| value as f64),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64_vec(&self) -> Option<Vec<i64>> {
+        match self {
+            Tag::ByteArray(values) => Some(values.iter().map(|value| (*value).into()).collect()),
+            Tag::IntArray(values) => Some(values.iter().map(|value| (*value).into()).collect()),
+            Tag::LongArray(values) => Some(values.clone()),
+            Tag::List(values) => values.iter().map(Tag::as_i64).collect(),
+            _ => None,
+        }
+    }
+}
+
+pub fn read_gzip<R: Read>(reader: R) -> Result<Tag, Error> {
+    read(GzDecoder::new(reader))
+}
+
+pub fn read<R: Read>(reader: R) -> Result<Tag, Error> {
+    read_limited(reader, MAX_NBT_SIZE)
+}
+
+// The input is decompressed, its size says nothing about the size of the upload.
+fn read_limited<R: Read>(reader: R, max_size: u64) -> Result<Tag, Error> {
+    let mut reader = reader.take(max_size);
+    let mut budget = max_size as usize;
+
+    let result = read_root(&mut reader, &mut budget);
+
+    match result {
+        Err(_) if reader.limit() == 0 => Err(invalid(&format!(
+            "The tags are bigger than {} bytes.",
+            max_size
+        ))),
+        result => result,
+    }
+}
+
+fn read_root<R: Read>(reader: &mut R, budget: &mut usize) -> Result<Tag, Error> {
+    if read_u8(reader)? != 10 {
+        return Err(invalid("The root tag isn't a compound."));
+    }
+
+    read_string(reader, budget)?;
+
+    read_payload(reader, 10, 0, budget)
+}
+
+// Takes the size of something about to be allocated from the budget.
+fn charge(budget: &mut usize, size: usize) -> Result<(), Error> {
+    *budget = budget
+        .checked_sub(size)
+        .ok_or_else(|| invalid("The tags are too big."))?;
+
+    Ok(())
+}
+
+fn read_payload<R: Read>(
+    reader: &mut R,
+    id: u8,
+    depth: usize,
+    budget: &mut usize,
+) -> Result<Tag, Error> {
+    if depth > MAX_DEPTH {
+        return Err(invalid("The tags are nested too deeply."));
+    }
+
+    charge(budget, size_of::<Tag>())?;
+
+    Ok(match id {
+        1 => Tag::Byte(read_u8(reader)? as i8),
+        2 => Tag::Short(i16::from_be_bytes(read_array(reader)?)),
+        3 => Tag::Int(i32::from_be_bytes(read_array(reader)?)),
+        4 => Tag::Long(i64::from_be_bytes(read_array(reader)?)),
+        5 => Tag::Float(f32::from_be_bytes(read_array(reader)?)),
+        6 => Tag::Double(f64::from_be_bytes(read_array(reader)?)),
+        7 => {
+            let length = read_length(reader)?;
+
+            charge(budget, length)?;
+
+            let bytes = read_bytes(reader, length)?;
+
+            Tag::ByteArray(bytes.into_iter().map(|byte| byte as i8).collect())
+        }
+        8 => Tag::String(read_string(reader, budget)?),
+        9 => {
+            let item_id = read_u8(reader)?;
+            let length = read_length(reader)?;
+            let mut items = Vec::new();
+
+            // Every item takes its own size from the budget once read, a length beyond what is
+            // left is rejected before any of them is.
+            if length.saturating_mul(size_of::<Tag>()) > *budget {
+                return Err(invalid("The tags are too big."));
+            }
+
+            for _ in 0..length {
+                items.push(read_payload(reader, item_id, depth + 1, budget)?);
+            }
+
+            Tag::List(items)
+        }
+        10 => {
+            let mut compound = HashMap::new();
+
+            loop {
+                let item_id = read_u8(reader)?;
+
+                if item_id == 0 {
+                    break;
+                }
+
+                let key = read_string(reader, budget)?;
+
+                compound.insert(key, read_payload(reader, item_id, depth + 1, budget)?);
+            }
+
+            Tag::Compound(compound)
+        }
+        11 => {
+            let length = read_length(reader)?;
+            let mut values = Vec::new();
+
+            charge(budget, length.saturating_mul(size_of::<i32>()))?;
+
+            for _ in 0..length {
+                values.push(i32::from_be_bytes(read_array(reader)?));
+            }
+
+            Tag::IntArray(values)
+        }
+        12 => {
+            let length = read_length(reader)?;
+            let mut values = Vec::new();
+
+            charge(budget, length.saturating_mul(size_of::<i64>()))?;
+
+            for _ in 0..length {
+                values.push(i64::from_be_bytes(read_array(reader)?));
+            }
+
+            Tag::LongArray(values)
+        }
+        _ => return Err(invalid(&format!("Unknown tag type {}.", id))),
+    })
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8, Error> {
+    Ok(read_array::<R, 1>(reader)?[0])
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> Result<[u8; N], Error> {
+    let mut buffer = [0; N];
+
+    reader.read_exact(&mut buffer).map_err(truncated)?;
+
+    Ok(buffer)
+}
+
+// The bytes are read through take, the buffer only grows with what is actually there.
+fn read_bytes<R: Read>(reader: &mut R, length: usize) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+
+    reader.take(length as u64).read_to_end(&mut buffer)?;
+
+    if buffer.len() != length {
+        return Err(invalid("The file is truncated."));
+    }
+
+    Ok(buffer)
+}
+
+fn read_length<R: Read>(reader: &mut R) -> Result<usize, Error> {
+    let length = i32::from_be_bytes(read_array(reader)?);
+
+    Ok(length.max(0) as usize)
+}
+
+// Strings are modified UTF-8, only differing from UTF-8 for characters no level.dat key uses.
+fn read_string<R: Read>(reader: &mut R, budget: &mut usize) -> Result<String, Error> {
+    let length = u16::from_be_bytes(read_array(reader)?);
+
+    charge(budget, length.into())?;
+
+    let bytes = read_bytes(reader, length.into())?;
+
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+fn truncated(err: Error) -> Error {
+    match err.kind() {
+        ErrorKind::UnexpectedEof => invalid("The file is truncated."),
+        _ => err,
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut bytes = (value.len() as u16).to_be_bytes().to_vec();
+        bytes.extend_from_slice(value.as_bytes());
+
+        bytes
+    }
+
+    fn named(id: u8, name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![id];
+        bytes.extend(string(name));
+        bytes.extend_from_slice(payload);
+
+        bytes
+    }
+
+    fn root(items: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = vec![10];
+        bytes.extend(string(""));
+        bytes.extend(items.concat());
+        bytes.push(0);
+
+        bytes
+    }
+
+    fn level_dat() -> Vec<u8> {
+        let mut version = named(8, "Name", &string("1.21.4"));
+        version.push(0);
+
+        let mut list = vec![3];
+        list.extend(2i32.to_be_bytes());
+        list.extend(7i32.to_be_bytes());
+        list.extend((-7i32).to_be_bytes());
+
+        let mut long_array = 1i32.to_be_bytes().to_vec();
+        long_array.extend(i64::MAX.to_be_bytes());
+
+        root(&[
+            named(1, "hardcore", &[1]),
+            named(2, "short", &300i16.to_be_bytes()),
+            named(4, "LastPlayed", &42i64.to_be_bytes()),
+            named(5, "float", &1.5f32.to_be_bytes()),
+            named(6, "double", &2.5f64.to_be_bytes()),
+            named(7, "bytes", &[0, 0, 0, 2, 255, 1]),
+            named(8, "LevelName", &string("lobby")),
+            named(9, "list", &list),
+            named(10, "Version", &version),
+            named(11, "ints", &[0, 0, 0, 1, 0, 0, 0, 9]),
+            named(12, "longs", &long_array),
+        ])
+    }
+
+    #[test]
+    fn read_every_tag_type() {
+        let tag = read(level_dat().as_slice()).unwrap();
+
+        assert_eq!(tag.get("hardcore").and_then(Tag::as_i64), Some(1));
+        assert_eq!(tag.get("short").and_then(Tag::as_i64), Some(300));
+        assert_eq!(tag.get("LastPlayed").and_then(Tag::as_i64), Some(42));
+        assert_eq!(tag.get("float").and_then(Tag::as_f64), Some(1.5));
+        assert_eq!(tag.get("double").and_then(Tag::as_f64), Some(2.5));
+        assert_eq!(
+            tag.get("bytes").and_then(Tag::as_i64_vec),
+            Some(vec![-1, 1])
+        );
+        assert_eq!(tag.get("LevelName").and_then(Tag::as_str), Some("lobby"));
+        assert_eq!(tag.get("list").and_then(Tag::as_i64_vec), Some(vec![7, -7]));
+        assert_eq!(
+            tag.get("Version")
+                .and_then(|version| version.get("Name"))
+                .and_then(Tag::as_str),
+            Some("1.21.4")
+        );
+        assert_eq!(tag.get("ints").and_then(Tag::as_i64_vec), Some(vec![9]));
+        assert_eq!(
+            tag.get("longs").and_then(Tag::as_i64_vec),
+            Some(vec![i64::MAX])
+        );
+        assert!(tag.get("missing").is_none());
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+
+        std::io::Write::write_all(&mut encoder, bytes).unwrap();
+
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn read_gzip_rejects_bombs() {
+        // A list of 2^31 - 1 bytes followed by 4 MiB of zeros, a few KiB once compressed.
+        let mut bytes = root(&[named(9, "list", &[1, 127, 255, 255, 255])]);
+        bytes.resize(bytes.len() + 4 * 1024 * 1024, 0);
+
+        let compressed = gzip(&bytes);
+
+        assert!(compressed.len() < 64 * 1024);
+        assert_eq!(
+            read_gzip(compressed.as_slice()).err().unwrap().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn read_limits_the_size() {
+        let oversized_array = root(&[named(7, "bytes", &[0, 0, 16, 0])]);
+        let mut oversized_input = oversized_array.clone();
+        oversized_input.resize(oversized_input.len() + 4096, 0);
+
+        let oversized_ints = root(&[named(11, "ints", &[127, 255, 255, 255])]);
+        let oversized_compound = root(&[named(8, "a", &string(&"a".repeat(2048)))]);
+
+        for bytes in [oversized_input, oversized_ints, oversized_compound] {
+            assert_eq!(
+                read_limited(bytes.as_slice(), 1024).err().unwrap().kind(),
+                ErrorKind::InvalidData
+            );
+        }
+
+        assert!(read_limited(level_dat().as_slice(), 1024).is_ok());
+    }
+
+    #[test]
+    fn read_gzip_decompresses() {
+        let tag = read_gzip(gzip(&level_dat()).as_slice()).unwrap();
+
+        assert_eq!(tag.get("LevelName").and_then(Tag::as_str), Some("lobby"));
+    }
+
+    #[test]
+    fn read_rejects_every_truncation() {
+        let bytes = level_dat();
+
+        for length in 0..bytes.len() {
+            let err = read(&bytes[..length]).err().unwrap();
+
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{}", length);
+        }
+    }
+
+    #[test]
+    fn read_rejects_malformed_tags() {
+        let not_compound = named(8, "", &string("lobby"));
+        let unknown_type = root(&[named(13, "unknown", &[])]);
+        let oversized_array = root(&[named(7, "bytes", &[127, 255, 255, 255, 1])]);
+        let oversized_list = root(&[named(9, "list", &[10, 127, 255, 255, 255])]);
+
+        for bytes in [not_compound, unknown_type, oversized_array, oversized_list] {
+            assert_eq!(
+                read(bytes.as_slice()).err().unwrap().kind(),
+                ErrorKind::InvalidData
+            );
+        }
+    }
+
+    #[test]
+    fn read_treats_negative_lengths_as_empty() {
+        let bytes = root(&[named(11, "ints", &(-5i32).to_be_bytes())]);
+        let tag = read(bytes.as_slice()).unwrap();
+
+        assert_eq!(tag.get("ints").and_then(Tag::as_i64_vec), Some(vec![]));
+    }
+
+    #[test]
+    fn read_limits_the_nesting() {
+        let nested = |depth: usize| {
+            let mut payload = vec![0];
+
+            for _ in 0..depth {
+                payload = [named(10, "a", &payload), vec![0]].concat();
+            }
+
+            [vec![10], string(""), payload].concat()
+        };
+
+        assert!(read(nested(MAX_DEPTH - 1).as_slice()).is_ok());
+        assert_eq!(
+            read(nested(MAX_DEPTH + 1).as_slice()).err().unwrap().kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(read(nested(10_000).as_slice()).is_err());
+    }
+}
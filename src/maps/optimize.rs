@@ -0,0 +1,678 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::Path;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use rocket::serde::Serialize;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use super::details::Bounds;
+use super::nbt::{self, Tag};
+
+// Folders and files of the world root only a running server needs.
+const STRIPPED_DIRECTORIES: [&str; 3] = ["playerdata", "stats", "advancements"];
+const STRIPPED_FILES: [&str; 1] = ["session.lock"];
+
+const AIR_BLOCKS: [&str; 3] = ["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+const SECTOR_SIZE: usize = 4096;
+const HEADER_SIZE: usize = 2 * SECTOR_SIZE;
+const REGION_CHUNKS: usize = 1024;
+
+#[derive(Serialize)]
+pub struct OptimizationReport {
+    // Sizes of the map zip, in bytes.
+    pub size_before: u64,
+    pub size_after: u64,
+    // Every file left out, stripped ones as well as region, entities and poi files left empty.
+    pub removed_files: usize,
+    pub removed_chunks: usize,
+    // Region files of which no chunk was kept.
+    pub removed_regions: usize,
+}
+
+// The kept chunks of every dimension, by the folder holding its region folder.
+type KeptChunks = HashMap<String, HashSet<(i64, i64)>>;
+
+// A region file, an entities file or a poi file, they all share the region format.
+struct RegionFile<'a> {
+    dimension: &'a str,
+    kind: &'a str,
+    region_x: i64,
+    region_z: i64,
+}
+
+struct Chunk {
+    index: usize,
+    timestamp: [u8; 4],
+    // The length prefixed payload, starting with the compression type.
+    payload: Vec<u8>,
+}
+
+// The source zip is read twice, once to find the chunks holding blocks or entities inside the
+// bounds, then to write every entry again, without the stripped files and with the region files
+// rewritten. The entities and poi files follow the chunks kept in the region files.
+pub fn optimize(
+    source: &Path,
+    destination: &Path,
+    bounds: Option<&Bounds>,
+) -> Result<OptimizationReport, Error> {
+    let mut archive = ZipArchive::new(File::open(source)?)?;
+    let mut report = OptimizationReport {
+        size_before: std::fs::metadata(source)?.len(),
+        size_after: 0,
+        removed_files: 0,
+        removed_chunks: 0,
+        removed_regions: 0,
+    };
+
+    let kept_chunks = find_kept_chunks(&mut archive, bounds, &mut report)?;
+
+    let mut zip = ZipWriter::new(File::create(destination)?);
+    let options = SimpleFileOptions::default();
+
+    for i in 0..archive.len() {
+        let name = archive.by_index_raw(i)?.name().to_string();
+
+        if is_stripped(&name) {
+            if !name.ends_with('/') {
+                report.removed_files += 1;
+            }
+
+            continue;
+        }
+
+        if let Some(region_file) = parse_region_file_name(&name) {
+            let kept = kept_chunks.get(region_file.dimension);
+            let is_kept = |index: usize| {
+                let (chunk_x, chunk_z) = get_chunk_coordinates(&region_file, index);
+
+                kept.is_some_and(|kept| kept.contains(&(chunk_x, chunk_z)))
+            };
+
+            let chunks = read_region(&mut archive.by_index(i)?, &name)?;
+            let total_chunks = chunks.len();
+            let chunks: Vec<Chunk> = chunks
+                .into_iter()
+                .filter(|chunk| is_kept(chunk.index))
+                .collect();
+
+            if chunks.is_empty() {
+                report.removed_files += 1;
+            } else if chunks.len() == total_chunks {
+                zip.raw_copy_file(archive.by_index_raw(i)?)?;
+            } else {
+                zip.start_file(name, options)?;
+                zip.write_all(&write_region(&chunks))?;
+            }
+
+            continue;
+        }
+
+        if let Some((dimension, chunk_x, chunk_z)) = parse_external_chunk_name(&name) {
+            let kept = kept_chunks.get(dimension);
+
+            if !kept.is_some_and(|kept| kept.contains(&(chunk_x, chunk_z))) {
+                report.removed_files += 1;
+                continue;
+            }
+        }
+
+        zip.raw_copy_file(archive.by_index_raw(i)?)?;
+    }
+
+    zip.finish()?;
+
+    report.size_after = std::fs::metadata(destination)?.len();
+
+    Ok(report)
+}
+
+fn find_kept_chunks(
+    archive: &mut ZipArchive<File>,
+    bounds: Option<&Bounds>,
+    report: &mut OptimizationReport,
+) -> Result<KeptChunks, Error> {
+    let names: Vec<String> = archive.file_names().map(String::from).collect();
+    let mut kept_chunks = find_entity_chunks(archive, &names, bounds)?;
+
+    for name in &names {
+        let region_file = match parse_region_file_name(name) {
+            Some(region_file) if region_file.kind == "region" => region_file,
+            _ => continue,
+        };
+
+        let chunks = read_region(&mut archive.by_name(name)?, name)?;
+        let mut kept_count = 0;
+
+        for chunk in &chunks {
+            let (chunk_x, chunk_z) = get_chunk_coordinates(&region_file, chunk.index);
+            let in_bounds = bounds.is_none_or(|bounds| bounds.contains_chunk(chunk_x, chunk_z));
+
+            let kept = kept_chunks
+                .get(region_file.dimension)
+                .is_some_and(|kept| kept.contains(&(chunk_x, chunk_z)));
+
+            if kept || (in_bounds && has_content(archive, &region_file, chunk, name)?) {
+                kept_chunks
+                    .entry(region_file.dimension.to_string())
+                    .or_default()
+                    .insert((chunk_x, chunk_z));
+                kept_count += 1;
+            }
+        }
+
+        report.removed_chunks += chunks.len() - kept_count;
+
+        if kept_count == 0 {
+            report.removed_regions += 1;
+        }
+    }
+
+    Ok(kept_chunks)
+}
+
+// Since 1.17 entities are stored apart, a chunk without blocks can still hold holograms or NPCs.
+fn find_entity_chunks(
+    archive: &mut ZipArchive<File>,
+    names: &[String],
+    bounds: Option<&Bounds>,
+) -> Result<KeptChunks, Error> {
+    let mut entity_chunks: KeptChunks = HashMap::new();
+
+    for name in names {
+        let region_file = match parse_region_file_name(name) {
+            Some(region_file) if region_file.kind == "entities" => region_file,
+            _ => continue,
+        };
+
+        let chunks = read_region(&mut archive.by_name(name)?, name)?;
+
+        for chunk in &chunks {
+            let (chunk_x, chunk_z) = get_chunk_coordinates(&region_file, chunk.index);
+            let in_bounds = bounds.is_none_or(|bounds| bounds.contains_chunk(chunk_x, chunk_z));
+
+            let has_entities = match read_chunk(archive, &region_file, chunk, name)? {
+                Some(tag) => has_entity_tags(&tag),
+                None => true,
+            };
+
+            if in_bounds && has_entities {
+                entity_chunks
+                    .entry(region_file.dimension.to_string())
+                    .or_default()
+                    .insert((chunk_x, chunk_z));
+            }
+        }
+    }
+
+    Ok(entity_chunks)
+}
+
+fn is_stripped(name: &str) -> bool {
+    match name.split_once('/') {
+        Some((directory, _)) => STRIPPED_DIRECTORIES.contains(&directory),
+        None => STRIPPED_FILES.contains(&name),
+    }
+}
+
+// <dimension>/<region|entities|poi>/r.<x>.<z>.mca, the dimension being empty for the overworld.
+fn parse_region_file_name(name: &str) -> Option<RegionFile<'_>> {
+    let (rest, file_name) = name.rsplit_once('/')?;
+    let (dimension, kind) = match rest.rsplit_once('/') {
+        Some((dimension, kind)) => (dimension, kind),
+        None => ("", rest),
+    };
+
+    if !["region", "entities", "poi"].contains(&kind) {
+        return None;
+    }
+
+    let coordinates = file_name.strip_prefix("r.")?.strip_suffix(".mca")?;
+    let (region_x, region_z) = coordinates.split_once('.')?;
+
+    Some(RegionFile {
+        dimension,
+        kind,
+        region_x: region_x.parse().ok()?,
+        region_z: region_z.parse().ok()?,
+    })
+}
+
+// Chunks too big for their region file are stored next to it, in c.<x>.<z>.mcc.
+fn parse_external_chunk_name(name: &str) -> Option<(&str, i64, i64)> {
+    let (rest, file_name) = name.rsplit_once('/')?;
+    let dimension = match rest.rsplit_once('/') {
+        Some((dimension, _)) => dimension,
+        None => "",
+    };
+
+    let coordinates = file_name.strip_prefix("c.")?.strip_suffix(".mcc")?;
+    let (chunk_x, chunk_z) = coordinates.split_once('.')?;
+
+    Some((dimension, chunk_x.parse().ok()?, chunk_z.parse().ok()?))
+}
+
+fn get_chunk_coordinates(region_file: &RegionFile, index: usize) -> (i64, i64) {
+    (
+        region_file.region_x * 32 + (index % 32) as i64,
+        region_file.region_z * 32 + (index / 32) as i64,
+    )
+}
+
+// A region file starts with the location of its 1024 chunks, in sectors of 4 KiB, followed by
+// their timestamps. Empty region files are left by the server and hold no chunk. A location holds
+// the sector count in a byte, a chunk can't span more sectors than it says, bigger chunks are
+// stored in their own file.
+fn read_region<R: Read>(reader: &mut R, name: &str) -> Result<Vec<Chunk>, Error> {
+    let mut bytes = Vec::new();
+
+    reader.read_to_end(&mut bytes)?;
+
+    if bytes.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let corrupted = || {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("The region file {} is corrupted.", name),
+        )
+    };
+
+    if bytes.len() < HEADER_SIZE {
+        return Err(corrupted());
+    }
+
+    let mut chunks = Vec::new();
+
+    for index in 0..REGION_CHUNKS {
+        let location = u32::from_be_bytes(bytes[index * 4..index * 4 + 4].try_into().unwrap());
+
+        if location == 0 {
+            continue;
+        }
+
+        let start = (location >> 8) as usize * SECTOR_SIZE;
+        let sector_count = (location & 0xff) as usize;
+        let length_end = start + 4;
+
+        if start < HEADER_SIZE || length_end > bytes.len() {
+            return Err(corrupted());
+        }
+
+        let length = u32::from_be_bytes(bytes[start..length_end].try_into().unwrap()) as usize;
+
+        if length == 0
+            || length_end + length > bytes.len()
+            || 4 + length > sector_count * SECTOR_SIZE
+        {
+            return Err(corrupted());
+        }
+
+        let timestamp_start = SECTOR_SIZE + index * 4;
+
+        chunks.push(Chunk {
+            index,
+            timestamp: bytes[timestamp_start..timestamp_start + 4]
+                .try_into()
+                .unwrap(),
+            payload: bytes[start..length_end + length].to_vec(),
+        });
+    }
+
+    Ok(chunks)
+}
+
+// The chunks come from read_region, each one fits in the sector count of its location.
+fn write_region(chunks: &[Chunk]) -> Vec<u8> {
+    let mut bytes = vec![0; HEADER_SIZE];
+
+    for chunk in chunks {
+        let sector = bytes.len() / SECTOR_SIZE;
+        let sector_count = chunk.payload.len().div_ceil(SECTOR_SIZE);
+        let location = ((sector as u32) << 8) | sector_count as u32;
+        let timestamp_start = SECTOR_SIZE + chunk.index * 4;
+
+        bytes[chunk.index * 4..chunk.index * 4 + 4].copy_from_slice(&location.to_be_bytes());
+        bytes[timestamp_start..timestamp_start + 4].copy_from_slice(&chunk.timestamp);
+        bytes.extend_from_slice(&chunk.payload);
+        bytes.resize((sector + sector_count) * SECTOR_SIZE, 0);
+    }
+
+    bytes
+}
+
+// Chunks compressed in a way that can't be read here are kept.
+fn has_content(
+    archive: &mut ZipArchive<File>,
+    region_file: &RegionFile,
+    chunk: &Chunk,
+    name: &str,
+) -> Result<bool, Error> {
+    Ok(match read_chunk(archive, region_file, chunk, name)? {
+        Some(tag) => has_block_tags(&tag) || has_entity_tags(&tag),
+        None => true,
+    })
+}
+
+// None when the chunk is compressed in a way that can't be read here.
+fn read_chunk(
+    archive: &mut ZipArchive<File>,
+    region_file: &RegionFile,
+    chunk: &Chunk,
+    name: &str,
+) -> Result<Option<Tag>, Error> {
+    let compression = chunk.payload[4];
+    let mut data = chunk.payload[5..].to_vec();
+
+    if compression & 0x80 != 0 {
+        let (chunk_x, chunk_z) = get_chunk_coordinates(region_file, chunk.index);
+        let external_name = format!(
+            "{}c.{}.{}.mcc",
+            name.trim_end_matches(|c| c != '/'),
+            chunk_x,
+            chunk_z
+        );
+
+        let mut external_file = archive.by_name(&external_name).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("The chunk file {} of {} is missing.", external_name, name),
+            )
+        })?;
+
+        // Still compressed, so never bigger than what nbt::read accepts once decompressed.
+        data.clear();
+        external_file
+            .by_ref()
+            .take(nbt::MAX_NBT_SIZE)
+            .read_to_end(&mut data)?;
+    }
+
+    let tag = match compression & 0x7f {
+        1 => nbt::read(GzDecoder::new(data.as_slice())),
+        2 => nbt::read(ZlibDecoder::new(data.as_slice())),
+        3 => nbt::read(data.as_slice()),
+        _ => return Ok(None),
+    }
+    .map_err(|err| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("A chunk of {} can't be read: {}", name, err),
+        )
+    })?;
+
+    Ok(Some(tag))
+}
+
+// Sections have a palette since 1.13, and an array of block ids before.
+fn has_block_tags(chunk: &Tag) -> bool {
+    let sections = chunk
+        .get("sections")
+        .or_else(|| chunk.get("Level").and_then(|level| level.get("Sections")));
+
+    let sections = match sections {
+        Some(Tag::List(sections)) => sections,
+        _ => return false,
+    };
+
+    sections.iter().any(|section| {
+        let palette = section
+            .get("block_states")
+            .and_then(|block_states| block_states.get("palette"))
+            .or_else(|| section.get("Palette"));
+
+        match palette {
+            Some(Tag::List(palette)) => palette.iter().any(|block| {
+                block
+                    .get("Name")
+                    .and_then(Tag::as_str)
+                    .is_some_and(|name| !AIR_BLOCKS.contains(&name))
+            }),
+            _ => section
+                .get("Blocks")
+                .and_then(Tag::as_i64_vec)
+                .is_some_and(|blocks| blocks.iter().any(|block| *block != 0)),
+        }
+    })
+}
+
+// Entities files hold them at the root, chunks before 1.17 in their level.
+fn has_entity_tags(chunk: &Tag) -> bool {
+    let entities = chunk
+        .get("Entities")
+        .or_else(|| chunk.get("Level").and_then(|level| level.get("Entities")));
+
+    matches!(entities, Some(Tag::List(entities)) if !entities.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(index: usize, data_length: usize) -> Chunk {
+        let mut payload = ((data_length + 1) as u32).to_be_bytes().to_vec();
+        payload.push(2);
+        payload.resize(5 + data_length, index as u8);
+
+        Chunk {
+            index,
+            timestamp: (index as u32).to_be_bytes(),
+            payload,
+        }
+    }
+
+    fn set_location(bytes: &mut [u8], index: usize, sector: u32, sector_count: u32) {
+        bytes[index * 4..index * 4 + 4]
+            .copy_from_slice(&((sector << 8) | sector_count).to_be_bytes());
+    }
+
+    fn compound(items: Vec<(&str, Tag)>) -> Tag {
+        Tag::Compound(
+            items
+                .into_iter()
+                .map(|(key, tag)| (key.to_string(), tag))
+                .collect(),
+        )
+    }
+
+    fn block(name: &str) -> Tag {
+        compound(vec![("Name", Tag::String(name.to_string()))])
+    }
+
+    #[test]
+    fn region_round_trip() {
+        let bytes = write_region(&[chunk(0, 10), chunk(5, SECTOR_SIZE * 2), chunk(1023, 0)]);
+
+        assert_eq!(bytes.len(), HEADER_SIZE + 5 * SECTOR_SIZE);
+
+        let chunks = read_region(&mut bytes.as_slice(), "r.0.0.mca").unwrap();
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.index)
+                .collect::<Vec<usize>>(),
+            vec![0, 5, 1023]
+        );
+
+        for chunk in chunks {
+            let expected = match chunk.index {
+                0 => self::chunk(0, 10),
+                5 => self::chunk(5, SECTOR_SIZE * 2),
+                _ => self::chunk(1023, 0),
+            };
+
+            assert_eq!(chunk.timestamp, expected.timestamp);
+            assert_eq!(chunk.payload, expected.payload);
+        }
+    }
+
+    #[test]
+    fn read_region_accepts_empty_files() {
+        assert!(read_region(&mut [].as_slice(), "r.0.0.mca")
+            .unwrap()
+            .is_empty());
+        assert!(
+            read_region(&mut vec![0; HEADER_SIZE].as_slice(), "r.0.0.mca")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn read_region_rejects_corrupted_headers() {
+        let valid = write_region(&[chunk(0, 10)]);
+
+        let truncated_header = valid[..HEADER_SIZE - 1].to_vec();
+        let truncated_chunk = valid[..HEADER_SIZE + 8].to_vec();
+
+        let mut inside_header = valid.clone();
+        set_location(&mut inside_header, 0, 1, 1);
+
+        let mut outside_file = valid.clone();
+        set_location(&mut outside_file, 0, 3, 1);
+
+        let mut zero_length = valid.clone();
+        zero_length[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&[0; 4]);
+
+        let mut long_length = valid.clone();
+        long_length[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        let mut too_few_sectors = write_region(&[chunk(0, SECTOR_SIZE)]);
+        set_location(&mut too_few_sectors, 0, 2, 1);
+
+        let mut no_sectors = valid.clone();
+        set_location(&mut no_sectors, 0, 2, 0);
+
+        for (case, bytes) in [
+            ("truncated header", truncated_header),
+            ("truncated chunk", truncated_chunk),
+            ("inside header", inside_header),
+            ("outside file", outside_file),
+            ("zero length", zero_length),
+            ("long length", long_length),
+            ("too few sectors", too_few_sectors),
+            ("no sectors", no_sectors),
+        ] {
+            let err = read_region(&mut bytes.as_slice(), "r.0.0.mca").err();
+
+            assert!(
+                err.is_some_and(|err| err.kind() == ErrorKind::InvalidData),
+                "{}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn parse_region_file_names() {
+        let region_file = parse_region_file_name("DIM-1/entities/r.-1.2.mca").unwrap();
+
+        assert_eq!(
+            (
+                region_file.dimension,
+                region_file.kind,
+                region_file.region_x,
+                region_file.region_z
+            ),
+            ("DIM-1", "entities", -1, 2)
+        );
+        assert_eq!(
+            parse_region_file_name("region/r.0.0.mca")
+                .unwrap()
+                .dimension,
+            ""
+        );
+        assert_eq!(get_chunk_coordinates(&region_file, 33), (-32 + 1, 64 + 1));
+
+        for name in [
+            "r.0.0.mca",
+            "data/r.0.0.mca",
+            "region/r.0.mca",
+            "region/r.a.0.mca",
+            "region/c.0.0.mcc",
+        ] {
+            assert!(parse_region_file_name(name).is_none(), "{}", name);
+        }
+
+        assert_eq!(
+            parse_external_chunk_name("dimensions/a/b/region/c.-40.3.mcc"),
+            Some(("dimensions/a/b", -40, 3))
+        );
+        assert_eq!(parse_external_chunk_name("region/r.0.0.mca"), None);
+    }
+
+    #[test]
+    fn has_block_tags_ignores_air() {
+        let section = |palette: Vec<Tag>| {
+            compound(vec![(
+                "block_states",
+                compound(vec![("palette", Tag::List(palette))]),
+            )])
+        };
+
+        let air = compound(vec![(
+            "sections",
+            Tag::List(vec![
+                section(vec![block("minecraft:air")]),
+                section(vec![
+                    block("minecraft:cave_air"),
+                    block("minecraft:void_air"),
+                ]),
+            ]),
+        )]);
+        let stone = compound(vec![(
+            "sections",
+            Tag::List(vec![section(vec![
+                block("minecraft:air"),
+                block("minecraft:stone"),
+            ])]),
+        )]);
+
+        assert!(!has_block_tags(&air));
+        assert!(has_block_tags(&stone));
+        assert!(!has_block_tags(&compound(Vec::new())));
+    }
+
+    #[test]
+    fn has_block_tags_reads_legacy_sections() {
+        let legacy = |blocks: Vec<i8>| {
+            compound(vec![(
+                "Level",
+                compound(vec![(
+                    "Sections",
+                    Tag::List(vec![compound(vec![("Blocks", Tag::ByteArray(blocks))])]),
+                )]),
+            )])
+        };
+
+        assert!(!has_block_tags(&legacy(vec![0; 16])));
+        assert!(has_block_tags(&legacy(vec![0, 1, 0])));
+    }
+
+    #[test]
+    fn has_entity_tags_reads_both_layouts() {
+        let entity = compound(vec![(
+            "id",
+            Tag::String("minecraft:armor_stand".to_string()),
+        )]);
+
+        assert!(has_entity_tags(&compound(vec![(
+            "Entities",
+            Tag::List(vec![entity])
+        )])));
+        assert!(!has_entity_tags(&compound(vec![(
+            "Entities",
+            Tag::List(Vec::new())
+        )])));
+        assert!(has_entity_tags(&compound(vec![(
+            "Level",
+            compound(vec![("Entities", Tag::List(vec![compound(Vec::new())]))])
+        )])));
+        assert!(!has_entity_tags(&compound(Vec::new())));
+    }
+}
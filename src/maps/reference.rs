@@ -0,0 +1,99 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use rocket::serde::{Deserialize, Serialize};
+
+// A map as referenced by a template, "name" follows the latest version, "name@version" is pinned.
+#[derive(Clone, PartialEq)]
+pub struct MapReference {
+    pub name: String,
+    pub version: Option<u32>,
+}
+
+// The version a reference pointed to when an archive or an image was made.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ResolvedMap {
+    pub name: String,
+    pub version: u32,
+}
+
+impl FromStr for MapReference {
+    type Err = &'static str;
+
+    fn from_str(reference: &str) -> Result<Self, Self::Err> {
+        match reference.split_once('@') {
+            Some((name, version)) => match version.parse() {
+                Ok(version) if version > 0 => Ok(MapReference {
+                    name: name.to_string(),
+                    version: Some(version),
+                }),
+                _ => Err("A map version must be a positive number."),
+            },
+            None => Ok(MapReference {
+                name: reference.to_string(),
+                version: None,
+            }),
+        }
+    }
+}
+
+impl Display for ResolvedMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.name, self.version)
+    }
+}
+
+pub fn format_resolved_maps(maps: &[ResolvedMap]) -> String {
+    maps.iter()
+        .map(ResolvedMap::to_string)
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(reference: &str) -> Result<(String, Option<u32>), &'static str> {
+        reference
+            .parse::<MapReference>()
+            .map(|reference| (reference.name, reference.version))
+    }
+
+    #[test]
+    fn from_str_reads_latest_and_pinned_references() {
+        assert_eq!(parse("lobby"), Ok(("lobby".to_string(), None)));
+        assert_eq!(parse("lobby@3"), Ok(("lobby".to_string(), Some(3))));
+    }
+
+    #[test]
+    fn from_str_rejects_invalid_versions() {
+        for reference in [
+            "lobby@",
+            "lobby@0",
+            "lobby@-1",
+            "lobby@v2",
+            "lobby@1@2",
+            "lobby@99999999999",
+        ] {
+            assert!(parse(reference).is_err(), "{:?}", reference);
+        }
+    }
+
+    #[test]
+    fn resolved_maps_are_formatted_as_pinned_references() {
+        let maps = [
+            ResolvedMap {
+                name: "lobby".to_string(),
+                version: 2,
+            },
+            ResolvedMap {
+                name: "arena".to_string(),
+                version: 10,
+            },
+        ];
+
+        assert_eq!(format_resolved_maps(&maps), "lobby@2,arena@10");
+        assert_eq!(format_resolved_maps(&[]), "");
+    }
+}
@@ -1,31 +1,87 @@
 use std::fs::File;
+use std::io::ErrorKind;
 
 use rocket::form::Form;
 use rocket::http::Status;
 use rocket::serde::json::serde_json::json;
+use rocket::serde::json::Json;
+use rocket::State;
 
+use crate::auth::guards::{Admin, Editor, ReadOnly};
+use crate::config::Config;
 use crate::responses::api_success::ApiSuccess;
 use crate::responses::file_upload::Upload;
-use crate::{global, templates, ApiError};
+use crate::safe_path;
+use crate::{templates, ApiError};
 
-use super::manager;
+use super::details::MapDetails;
+use super::reference::MapReference;
+use super::{manager, trash};
 
-#[post("/<name>/push", data = "<data>")]
-pub async fn push_map(name: String, mut data: Form<Upload<'_>>) -> Result<ApiSuccess, ApiError> {
-    let file = &mut data.upload;
-    let map_file_path_str = format!("{}/{}.zip", global::MAPS_DIR, &name);
+#[post("/<name>/push?<optimize>", data = "<data>")]
+pub async fn push_map(
+    _role: Editor,
+    name: String,
+    optimize: Option<bool>,
+    mut data: Form<Upload<'_>>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
 
-    file.persist_to(map_file_path_str)
-        .await
+    let (map_version, report) =
+        manager::install(&mut data.upload, &name, optimize.unwrap_or(false)).await?;
+
+    templates::cache::invalidate_map(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::data(json!({
+        "success": "The map has been pushed.",
+        "version": map_version,
+        "optimization": report,
+    })))
+}
+
+// The latest version unless one is asked for, the result is pushed as a new version.
+#[post("/<name>/optimize?<version>")]
+pub async fn optimize(
+    _role: Editor,
+    name: String,
+    version: Option<u32>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    let reference = MapReference { name, version };
+    let resolved_map = manager::resolve(&reference).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => ApiError::new(&err.to_string(), Status::NotFound),
+        _ => ApiError::default(err.to_string().as_str()),
+    })?;
+
+    let (map_version, report) =
+        manager::optimize_version(&resolved_map.name, resolved_map.version).await?;
+
+    templates::cache::invalidate_map(&resolved_map.name)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    Ok(ApiSuccess::default("The map has been pushed."))
+    Ok(ApiSuccess::data(json!({
+        "success": "The map has been optimized.",
+        "version": map_version,
+        "optimization": report,
+    })))
 }
 
-#[delete("/<name>/delete")]
-pub async fn delete(name: String) -> Result<ApiSuccess, ApiError> {
+#[delete("/<name>/delete?<permanent>")]
+pub async fn delete(
+    _role: Admin,
+    name: String,
+    permanent: Option<bool>,
+    config: &State<Config>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    // Held until the map is gone, so no template can start using it in the meantime.
+    let _maps_guard = manager::lock_maps();
+
     if !manager::map_exist(&name) {
-        return Err(ApiError::default("The map doesn't exist."));
+        return Err(ApiError::new("The map doesn't exist.", Status::NotFound));
     }
 
     let templates = templates::manager::get_templates()
@@ -34,7 +90,7 @@ pub async fn delete(name: String) -> Result<ApiSuccess, ApiError> {
     let mut template_using_map = false;
 
     for template in templates {
-        if template.maps.contains(&name.to_string()) {
+        if template.uses_map(&name) {
             template_using_map = true;
             break;
         }
@@ -47,39 +103,169 @@ pub async fn delete(name: String) -> Result<ApiSuccess, ApiError> {
         ));
     }
 
-    Ok(ApiSuccess::default("The map has been deleted."))
+    let message = if permanent.unwrap_or(false) || config.map_trash_retention_days == 0 {
+        std::fs::remove_dir_all(manager::get_map_path(&name))
+            .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+        "The map has been deleted."
+    } else {
+        trash::trash_map(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+        "The map has been moved to the trash."
+    };
+
+    templates::cache::invalidate_map(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    trash::purge_expired(config.map_trash_retention_days)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default(message))
 }
 
-#[get("/")]
-pub async fn get_maps() -> Result<ApiSuccess, ApiError> {
-    let maps_dir = global::MAPS_DIR;
-    let mut maps = Vec::new();
+#[delete("/<name>/<version>/delete")]
+pub async fn delete_version(
+    _role: Admin,
+    name: String,
+    version: u32,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    let _maps_guard = manager::lock_maps();
+
+    if !manager::map_exist(&name) {
+        return Err(ApiError::new("The map doesn't exist.", Status::NotFound));
+    }
 
-    let map_files = std::fs::read_dir(maps_dir)
-        .map_err(|err| ApiError::default(err.to_string().as_str()))?
-        .filter_map(|file| file.ok())
-        .filter(|file| file.path().is_file());
+    let templates = templates::manager::get_templates()
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    for file in map_files {
-        let path = file.path();
-        let file_name_os_str = path.file_name().unwrap();
-        let file_name_str = file_name_os_str.to_str().unwrap();
+    // Snapshots can still be archived, built or rolled back to, a version they pin is kept.
+    for template in templates {
+        let snapshot_templates = templates::snapshots::get_snapshot_templates(&template.name)
+            .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-        if file_name_str.ends_with(".zip") {
-            maps.push(file_name_str.replace(".zip", ""));
+        if template.uses_map_version(&name, version)
+            || snapshot_templates
+                .iter()
+                .any(|snapshot_template| snapshot_template.uses_map_version(&name, version))
+        {
+            return Err(ApiError::new(
+                "Some templates or their snapshots are using this version of the map.",
+                Status::Conflict,
+            ));
         }
     }
 
+    manager::delete_version(&name, version)?;
+
+    templates::cache::invalidate_map(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The map version has been deleted."))
+}
+
+#[post("/<name>/restore")]
+pub async fn restore(_role: Admin, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    let _maps_guard = manager::lock_maps();
+
+    if manager::map_exist(&name) {
+        return Err(ApiError::new(
+            "A map with this name already exists.",
+            Status::Conflict,
+        ));
+    }
+
+    trash::restore_map(&name).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => ApiError::new(&err.to_string(), Status::NotFound),
+        _ => ApiError::default(err.to_string().as_str()),
+    })?;
+
+    Ok(ApiSuccess::default("The map has been restored."))
+}
+
+#[get("/trash")]
+pub async fn get_trash(_role: ReadOnly, config: &State<Config>) -> Result<ApiSuccess, ApiError> {
+    let trashed_maps = trash::get_trashed_maps(config.map_trash_retention_days)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::data(json!({ "maps": trashed_maps })))
+}
+
+// Tags can be given several times, a map has to carry all of them.
+#[get("/?<mode>&<tag>")]
+pub async fn get_maps(
+    _role: ReadOnly,
+    mode: Option<String>,
+    tag: Vec<String>,
+) -> Result<ApiSuccess, ApiError> {
+    let maps = manager::get_maps(mode.as_deref(), &tag)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
     Ok(ApiSuccess::data(json!(maps)))
 }
 
-#[get("/<name>/get")]
-pub async fn get_map(name: String) -> Result<File, ApiError> {
+#[put("/<name>/details", data = "<data>")]
+pub async fn update_details(
+    _role: Editor,
+    name: String,
+    data: Json<MapDetails>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    let mut details = data.into_inner();
+
+    details
+        .validate()
+        .map_err(|err| ApiError::new(err, Status::BadRequest))?;
+    details.normalize();
+
+    // Held so the map can't be moved to the trash while its details are written.
+    let _maps_guard = manager::lock_maps();
+
+    if !manager::map_exist(&name) {
+        return Err(ApiError::new("The map doesn't exist.", Status::NotFound));
+    }
+
+    manager::save_details(&name, &details)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The map details have been updated."))
+}
+
+#[get("/<name>")]
+pub async fn get_map_details(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::map_exist(&name) {
-        return Err(ApiError::default("The map doesn't exist."));
+        return Err(ApiError::new("The map doesn't exist.", Status::NotFound));
     }
 
-    let map_file_path = manager::get_map_path(&name);
+    let map = manager::get_map(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::data(json!(map)))
+}
+
+// The latest version unless one is asked for.
+#[get("/<name>/get?<version>")]
+pub async fn get_map(
+    _role: ReadOnly,
+    name: String,
+    version: Option<u32>,
+) -> Result<File, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    let reference = MapReference { name, version };
+    let resolved_map = manager::resolve(&reference).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => ApiError::new(&err.to_string(), Status::NotFound),
+        _ => ApiError::default(err.to_string().as_str()),
+    })?;
 
-    Ok(File::open(map_file_path).unwrap())
+    File::open(manager::get_version_path(
+        &resolved_map.name,
+        resolved_map.version,
+    ))
+    .map_err(|err| ApiError::default(err.to_string().as_str()))
 }
@@ -0,0 +1,142 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rocket::serde::Serialize;
+
+use crate::global;
+
+use super::manager;
+
+#[derive(Serialize)]
+pub struct TrashedMap {
+    pub name: String,
+    pub versions: usize,
+    pub size: u64,
+    pub deleted_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub fn get_trash_path(name: &str) -> String {
+    format!("{}/{}", global::MAPS_TRASH_DIR, name)
+}
+
+// A map can be deleted several times, every deletion keeps its versions under its timestamp.
+pub fn get_trashed_map_path(name: &str, deleted_at: &DateTime<Utc>) -> String {
+    format!("{}/{}", get_trash_path(name), deleted_at.timestamp_millis())
+}
+
+pub fn trash_map(name: &str) -> Result<(), Error> {
+    std::fs::create_dir_all(get_trash_path(name))?;
+
+    std::fs::rename(
+        manager::get_map_path(name),
+        get_trashed_map_path(name, &Utc::now()),
+    )
+}
+
+pub fn get_trashed_maps(retention_days: u32) -> Result<Vec<TrashedMap>, Error> {
+    let mut trashed_maps = Vec::new();
+
+    let trash_directories = std::fs::read_dir(global::MAPS_TRASH_DIR)?
+        .filter_map(|dir| dir.ok())
+        .filter(|dir| dir.path().is_dir());
+
+    for dir in trash_directories {
+        let name = dir.file_name().to_string_lossy().to_string();
+
+        for (deleted_at, path) in get_deletions(&dir.path())? {
+            let mut versions = 0;
+            let mut size = 0;
+
+            for file in std::fs::read_dir(path)?.filter_map(|file| file.ok()) {
+                if file.path().extension().is_some_and(|ext| ext == "zip") {
+                    versions += 1;
+                    size += file.metadata()?.len();
+                }
+            }
+
+            trashed_maps.push(TrashedMap {
+                name: name.clone(),
+                versions,
+                size,
+                deleted_at,
+                expires_at: deleted_at + Duration::days(retention_days.into()),
+            });
+        }
+    }
+
+    trashed_maps.sort_by_key(|trashed_map| std::cmp::Reverse(trashed_map.deleted_at));
+
+    Ok(trashed_maps)
+}
+
+// Restores the latest deletion of the map.
+pub fn restore_map(name: &str) -> Result<(), Error> {
+    let trash_path_str = get_trash_path(name);
+
+    let (_, path) = match Path::new(&trash_path_str).is_dir() {
+        true => get_deletions(Path::new(&trash_path_str))?.pop(),
+        false => None,
+    }
+    .ok_or_else(|| Error::new(ErrorKind::NotFound, "The map isn't in the trash."))?;
+
+    std::fs::rename(path, manager::get_map_path(name))?;
+
+    remove_if_empty(Path::new(&trash_path_str))
+}
+
+pub fn purge_expired(retention_days: u32) -> Result<(), Error> {
+    let expired_before = Utc::now() - Duration::days(retention_days.into());
+
+    let trash_directories = std::fs::read_dir(global::MAPS_TRASH_DIR)?
+        .filter_map(|dir| dir.ok())
+        .filter(|dir| dir.path().is_dir());
+
+    for dir in trash_directories {
+        for (deleted_at, path) in get_deletions(&dir.path())? {
+            if deleted_at < expired_before {
+                std::fs::remove_dir_all(path)?;
+            }
+        }
+
+        remove_if_empty(&dir.path())?;
+    }
+
+    Ok(())
+}
+
+// Returns the deletions of a map, oldest first.
+fn get_deletions(trash_path: &Path) -> Result<Vec<(DateTime<Utc>, std::path::PathBuf)>, Error> {
+    let mut deletions = Vec::new();
+
+    let deletion_directories = std::fs::read_dir(trash_path)?
+        .filter_map(|dir| dir.ok())
+        .filter(|dir| dir.path().is_dir());
+
+    for dir in deletion_directories {
+        let path = dir.path();
+
+        let deleted_at = path
+            .file_name()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok())
+            .and_then(|millis| Utc.timestamp_millis_opt(millis).single());
+
+        if let Some(deleted_at) = deleted_at {
+            deletions.push((deleted_at, path));
+        }
+    }
+
+    deletions.sort_by_key(|(deleted_at, _)| *deleted_at);
+
+    Ok(deletions)
+}
+
+fn remove_if_empty(dir_path: &Path) -> Result<(), Error> {
+    if std::fs::read_dir(dir_path)?.next().is_none() {
+        std::fs::remove_dir(dir_path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use rocket::serde::Deserialize;
+use rocket::serde::Serialize;
+
+use crate::builds::build::Mode;
+
+use super::parent::Type;
+
+const DEFAULT_BASE_IMAGE: &str = "openjdk:15-jdk-alpine";
+
+const SERVER_ENTRYPOINT: &str = "java -Xms${MIN_HEAP_SIZE} -Xmx${MAX_HEAP_SIZE} -XX:+UseG1GC -XX:+ParallelRefProcEnabled -XX:MaxGCPauseMillis=200 -XX:+UnlockExperimentalVMOptions -XX:+DisableExplicitGC -XX:+AlwaysPreTouch -XX:G1HeapWastePercent=5 -XX:G1MixedGCCountTarget=4 -XX:G1MixedGCLiveThresholdPercent=90 -XX:G1RSetUpdatingPauseTimePercent=5 -XX:SurvivorRatio=32 -XX:+PerfDisableSharedMem -XX:MaxTenuringThreshold=1 -XX:G1NewSizePercent=30 -XX:G1MaxNewSizePercent=40 -XX:G1HeapRegionSize=8M -XX:G1ReservePercent=20 -XX:InitiatingHeapOccupancyPercent=15 -Dusing.aikars.flags=https://mcflags.emc.gs/ -Daikars.new.flags=true -jar -Dcom.mojang.eula.agree=true -DIknowWhatImDoingISwear server.jar --level-name ${DEFAULT_MAP_NAME} nogui --noconsole";
+
+const PROXY_ENTRYPOINT: &str = "java -Xms${MIN_HEAP_SIZE} -Xmx${MAX_HEAP_SIZE} -XX:+UseG1GC -XX:G1HeapRegionSize=4M -XX:+UnlockExperimentalVMOptions -XX:+ParallelRefProcEnabled -XX:+AlwaysPreTouch -XX:MaxInlineLevel=15 -jar server.jar";
+
+// API_HOST is the bare host name of the API, which listens on port 8000.
+const REMOTE_DOWNLOAD_COMMAND: &str = "wget --header \"Authorization: Bearer ${API_TOKEN}\" -O template.zip http://${API_HOST}:8000/templates/${TEMPLATE_NAME}/zip && unzip template.zip && rm template.zip && clear";
+
+// Base images don't share a package manager, the download tools are installed with whichever one
+// is there when they are missing.
+const REMOTE_TOOLS_COMMAND: &str = "(command -v wget && command -v unzip) || apk add --no-cache wget unzip || (apt-get update && apt-get install -y --no-install-recommends wget unzip && rm -rf /var/lib/apt/lists/*) || microdnf install -y wget unzip";
+
+#[derive(Serialize, Deserialize)]
+pub struct Image {
+    pub base_image: Option<String>,
+    pub jdk_version: Option<u16>,
+    pub entrypoint: Option<String>,
+}
+
+impl Image {
+    pub fn get_base_image(&self) -> String {
+        match (&self.base_image, self.jdk_version) {
+            (Some(base_image), _) => base_image.clone(),
+            (None, Some(jdk_version)) => format!("eclipse-temurin:{}-jdk-alpine", jdk_version),
+            (None, None) => DEFAULT_BASE_IMAGE.to_string(),
+        }
+    }
+
+    pub fn get_entrypoint(&self, t: &Type) -> &str {
+        match (&self.entrypoint, t) {
+            (Some(entrypoint), _) => entrypoint,
+            (None, Type::Server) => SERVER_ENTRYPOINT,
+            (None, Type::Proxy) => PROXY_ENTRYPOINT,
+        }
+    }
+
+    pub fn to_dockerfile(&self, t: &Type, mode: Mode) -> String {
+        let mut lines = vec![
+            format!("FROM {}", self.get_base_image()),
+            String::new(),
+            String::from("ARG TEMPLATE_NAME"),
+            String::from("ENV TEMPLATE_NAME $TEMPLATE_NAME"),
+            String::new(),
+            String::from("ARG DEFAULT_MAP_NAME"),
+            String::from("ENV DEFAULT_MAP_NAME $DEFAULT_MAP_NAME"),
+            String::new(),
+            String::from("ARG MIN_HEAP_SIZE"),
+            String::from("ENV MIN_HEAP_SIZE $MIN_HEAP_SIZE"),
+            String::new(),
+            String::from("ARG MAX_HEAP_SIZE"),
+            String::from("ENV MAX_HEAP_SIZE $MAX_HEAP_SIZE"),
+            String::new(),
+        ];
+
+        let entrypoint = self.get_entrypoint(t);
+
+        match mode {
+            Mode::Remote => {
+                lines.push(String::from("ARG API_HOST"));
+                lines.push(String::from("ENV API_HOST $API_HOST"));
+                lines.push(String::new());
+                lines.push(String::from(
+                    "# API_TOKEN is given when the container is run, it isn't stored in the image.",
+                ));
+                lines.push(String::new());
+                lines.push(String::from("WORKDIR /data/${TEMPLATE_NAME}"));
+                lines.push(String::new());
+                lines.push(format!("RUN {}", REMOTE_TOOLS_COMMAND));
+                lines.push(String::new());
+                lines.push(format!("CMD {} && {}", REMOTE_DOWNLOAD_COMMAND, entrypoint));
+            }
+            Mode::Baked => {
+                lines.push(String::from("WORKDIR /data/${TEMPLATE_NAME}"));
+                lines.push(String::new());
+                lines.push(String::from("COPY content/ ./"));
+                lines.push(String::new());
+                lines.push(format!("CMD {}", entrypoint));
+            }
+        }
+
+        lines.join("\n")
+    }
+}
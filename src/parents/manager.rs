@@ -2,6 +2,7 @@ use std::{fs::File, io::Error, path::Path};
 
 use rocket::serde::json::serde_json;
 
+use crate::builds::build::Mode;
 use crate::global;
 
 use super::parent::Parent;
@@ -18,6 +19,15 @@ pub fn get_parent_file_path(name: &str) -> String {
     format!("{}/parents.epsilon", get_parent_path(name))
 }
 
+// Each build mode has its own Dockerfile, a remote one downloads the archive while a baked one
+// copies the content.
+pub fn get_parent_dockerfile_path(name: &str, mode: Mode) -> String {
+    match mode {
+        Mode::Remote => format!("{}/dockerfile.epsilon", get_parent_path(name)),
+        Mode::Baked => format!("{}/dockerfile.baked.epsilon", get_parent_path(name)),
+    }
+}
+
 pub fn parent_exist(name: &str) -> bool {
     let parent_file_path_str = &get_parent_file_path(name);
     let parent_file_path = Path::new(parent_file_path_str);
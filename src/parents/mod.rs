@@ -1,3 +1,4 @@
+pub mod image;
 pub mod manager;
 pub mod parent;
 pub mod routes;
@@ -1,6 +1,8 @@
 use rocket::serde::Deserialize;
 use rocket::serde::Serialize;
 
+use super::image::Image;
+
 #[derive(Serialize, Deserialize)]
 pub struct Parent {
     pub name: String,
@@ -8,6 +10,9 @@ pub struct Parent {
     #[serde(rename = "type")]
     pub t: Type,
     pub description: String,
+    pub image: Option<Image>,
+    // The Minecraft version of the server, plugins requiring a newer API are reported.
+    pub minecraft_version: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
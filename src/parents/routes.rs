@@ -1,14 +1,18 @@
 use std::fs::File;
+use std::path::Path;
 
 use rocket::form::Form;
 use rocket::serde::json::serde_json::json;
 use rocket::serde::json::{serde_json, Json};
 
+use crate::auth::guards::{Admin, Editor, ReadOnly};
+use crate::builds::build::Mode;
 use crate::parents::parent::Parent;
 use crate::responses::api_error::ApiError;
 use crate::responses::api_success::ApiSuccess;
-use crate::responses::file_upload::Upload;
-use crate::{global, templates, Status};
+use crate::responses::file_upload::{NestedUpload, Upload};
+use crate::safe_path;
+use crate::{files, global, plugins, templates, Status};
 
 use super::manager;
 
@@ -17,7 +21,7 @@ fn init_dirs(name: &str) -> std::io::Result<()> {
 }
 
 #[get("/")]
-pub async fn get_parents() -> Result<ApiSuccess, ApiError> {
+pub async fn get_parents(_role: ReadOnly) -> Result<ApiSuccess, ApiError> {
     let mut parents: Vec<Parent> = Vec::new();
 
     let parent_directories = std::fs::read_dir(global::PARENTS_DIR)
@@ -31,8 +35,8 @@ pub async fn get_parents() -> Result<ApiSuccess, ApiError> {
         let directory_name = directory_name_os_str.to_str().unwrap();
         let current_parent_result = manager::get_parent_obj(directory_name);
 
-        if let Ok(..) = current_parent_result {
-            parents.push(current_parent_result.unwrap());
+        if let Ok(current_parent) = current_parent_result {
+            parents.push(current_parent);
         }
     }
 
@@ -40,7 +44,9 @@ pub async fn get_parents() -> Result<ApiSuccess, ApiError> {
 }
 
 #[get("/<name>")]
-pub async fn get_parent(name: String) -> Result<ApiSuccess, ApiError> {
+pub async fn get_parent(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::parent_exist(&name) {
         return Err(ApiError::new(
             "The parent doesn't exist.",
@@ -55,10 +61,12 @@ pub async fn get_parent(name: String) -> Result<ApiSuccess, ApiError> {
 }
 
 #[post("/create", data = "<data>")]
-pub async fn create(data: Json<Parent>) -> Result<ApiSuccess, ApiError> {
+pub async fn create(_role: Editor, data: Json<Parent>) -> Result<ApiSuccess, ApiError> {
     let parent = data.into_inner();
     let name = &parent.name;
 
+    safe_path::validate_name(name)?;
+
     if manager::parent_exist(name) {
         return Err(ApiError::new("The parent already exist.", Status::Conflict));
     }
@@ -76,7 +84,9 @@ pub async fn create(data: Json<Parent>) -> Result<ApiSuccess, ApiError> {
 }
 
 #[delete("/<name>/delete")]
-pub async fn delete(name: String) -> Result<ApiSuccess, ApiError> {
+pub async fn delete(_role: Admin, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::parent_exist(&name) {
         return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
     }
@@ -108,42 +118,314 @@ pub async fn delete(name: String) -> Result<ApiSuccess, ApiError> {
     Ok(ApiSuccess::default("The parent has been deleted."))
 }
 
+#[put("/<name>/update", data = "<data>")]
+pub async fn update(
+    _role: Editor,
+    name: String,
+    data: Json<Parent>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    let parent = data.into_inner();
+
+    if parent.name != name {
+        return Err(ApiError::new(
+            "The parent can't be renamed.",
+            Status::BadRequest,
+        ));
+    }
+
+    let parent_file_path_str = manager::get_parent_file_path(&name);
+    let parent_file = File::create(&parent_file_path_str)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    serde_json::to_writer_pretty(parent_file, &parent)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The parent has been updated."))
+}
+
 #[post("/<name>/plugins/push", data = "<data>")]
-pub async fn push_plugin(name: String, mut data: Form<Upload<'_>>) -> Result<ApiSuccess, ApiError> {
+pub async fn push_plugin(
+    _role: Editor,
+    name: String,
+    mut data: Form<Upload<'_>>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::parent_exist(&name) {
         return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
     }
 
     let file = &mut data.upload;
-    let raw_name = file.raw_name().unwrap();
-    let file_name = raw_name.dangerous_unsafe_unsanitized_raw();
+    let file_name = safe_path::get_upload_file_name(file)?;
 
     let plugin_path_str = manager::get_parent_plugins_path(&name);
-    let plugin_file_path = format!("{}/{}", plugin_path_str, file_name);
+    let plugin_file_path = safe_path::join(&plugin_path_str, &file_name)?;
 
-    file.persist_to(plugin_file_path)
-        .await
+    plugins::manager::install(file, &plugin_file_path, None).await?;
+
+    templates::cache::invalidate_parent(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    let parent = manager::get_parent_obj(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    let validation = plugins::validation::validate_parent(&parent)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::data(json!({
+        "success": "The plugin has been pushed.",
+        "validation": validation
+    })))
+}
+
+#[get("/<name>/plugins")]
+pub async fn get_plugins(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    let plugins = plugins::manager::get_plugins(&manager::get_parent_plugins_path(&name))
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    Ok(ApiSuccess::default("The plugin has been pushed."))
+    Ok(ApiSuccess::data(json!(plugins)))
+}
+
+#[delete("/<name>/plugins/<plugin>/delete")]
+pub async fn delete_plugin(
+    _role: Admin,
+    name: String,
+    plugin: String,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    let plugin_file_path = safe_path::join(&manager::get_parent_plugins_path(&name), &plugin)?;
+
+    if !plugin_file_path.is_file() {
+        return Err(ApiError::new("The plugin doesn't exist.", Status::NotFound));
+    }
+
+    plugins::manager::remove(&plugin_file_path)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    templates::cache::invalidate_parent(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The plugin has been deleted."))
+}
+
+// The uploaded jar takes the place of the given one, which may have another name.
+#[post("/<name>/plugins/<plugin>/replace", data = "<data>")]
+pub async fn replace_plugin(
+    _role: Editor,
+    name: String,
+    plugin: String,
+    mut data: Form<Upload<'_>>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    let file = &mut data.upload;
+    let file_name = safe_path::get_upload_file_name(file)?;
+
+    let plugin_path_str = manager::get_parent_plugins_path(&name);
+    let replaced_file_path = safe_path::join(&plugin_path_str, &plugin)?;
+    let plugin_file_path = safe_path::join(&plugin_path_str, &file_name)?;
+
+    if !replaced_file_path.is_file() {
+        return Err(ApiError::new("The plugin doesn't exist.", Status::NotFound));
+    }
+
+    plugins::manager::install(file, &plugin_file_path, Some(&replaced_file_path)).await?;
+
+    templates::cache::invalidate_parent(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    let parent = manager::get_parent_obj(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    let validation = plugins::validation::validate_parent(&parent)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::data(json!({
+        "success": "The plugin has been replaced.",
+        "validation": validation
+    })))
 }
 //
 #[post("/<name>/main/push", data = "<data>")]
-pub async fn push_file(name: String, mut data: Form<Upload<'_>>) -> Result<ApiSuccess, ApiError> {
+pub async fn push_file(
+    _role: Editor,
+    name: String,
+    mut data: Form<Upload<'_>>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::parent_exist(&name) {
         return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
     }
 
     let file = &mut data.upload;
-    let raw_name = file.raw_name().unwrap();
-    let file_name = raw_name.dangerous_unsafe_unsanitized_raw();
+    let file_name = safe_path::get_upload_file_name(file)?;
 
     let parent_path_str = manager::get_parent_path(&name);
-    let new_file_path = format!("{}/{}", parent_path_str, file_name);
+    let new_file_path = safe_path::join(&parent_path_str, &file_name)?;
 
     file.persist_to(new_file_path)
         .await
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
+    templates::cache::invalidate_parent(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
     Ok(ApiSuccess::default("The file has been pushed."))
 }
+
+// The destination is a path relative to the parent directory, missing directories are created.
+#[post("/<name>/files/push", data = "<data>")]
+pub async fn push_nested_file(
+    _role: Editor,
+    name: String,
+    mut data: Form<NestedUpload<'_>>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    let new_file_path = files::prepare_upload_path(&manager::get_parent_path(&name), &data.path)?;
+
+    data.upload
+        .persist_to(new_file_path)
+        .await
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    templates::cache::invalidate_parent(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The file has been pushed."))
+}
+
+#[post("/<name>/dockerfile/push?<mode>", data = "<data>")]
+pub async fn push_dockerfile(
+    _role: Editor,
+    name: String,
+    mode: Option<Mode>,
+    mut data: Form<Upload<'_>>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    let file = &mut data.upload;
+    let dockerfile_path_str = manager::get_parent_dockerfile_path(&name, mode.unwrap_or_default());
+
+    file.persist_to(dockerfile_path_str)
+        .await
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The Dockerfile has been pushed."))
+}
+
+#[delete("/<name>/dockerfile/delete?<mode>")]
+pub async fn delete_dockerfile(
+    _role: Admin,
+    name: String,
+    mode: Option<Mode>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    let dockerfile_path_str = manager::get_parent_dockerfile_path(&name, mode.unwrap_or_default());
+
+    if !Path::new(&dockerfile_path_str).exists() {
+        return Err(ApiError::new(
+            "The parent doesn't have a Dockerfile for this mode.",
+            Status::NotFound,
+        ));
+    }
+
+    std::fs::remove_file(dockerfile_path_str)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The Dockerfile has been deleted."))
+}
+
+#[get("/<name>/files")]
+pub async fn get_files(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    let files = files::list_files(&manager::get_parent_path(&name))?;
+
+    Ok(ApiSuccess::data(json!(files)))
+}
+
+#[get("/<name>/files/download?<path>")]
+pub async fn download_file(_role: ReadOnly, name: String, path: String) -> Result<File, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    files::open_file(&manager::get_parent_path(&name), &path)
+}
+
+#[put("/<name>/files/rename?<path>&<to>")]
+pub async fn rename_file(
+    _role: Editor,
+    name: String,
+    path: String,
+    to: String,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    files::rename_file(&manager::get_parent_path(&name), &path, &to)?;
+
+    templates::cache::invalidate_parent(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The file has been renamed."))
+}
+
+#[delete("/<name>/files/delete?<path>")]
+pub async fn delete_file(_role: Admin, name: String, path: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::parent_exist(&name) {
+        return Err(ApiError::new("The parent doesn't exist.", Status::NotFound));
+    }
+
+    files::delete_file(&manager::get_parent_path(&name), &path)?;
+
+    templates::cache::invalidate_parent(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The file has been deleted."))
+}
@@ -0,0 +1,182 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use rocket::fs::TempFile;
+use rocket::http::Status;
+use rocket::serde::json::serde_json;
+use rocket::serde::Serialize;
+
+use crate::responses::api_error::ApiError;
+use crate::{files, global};
+
+use super::metadata::{self, PluginMetadata};
+
+#[derive(Serialize)]
+pub struct Plugin {
+    pub file_name: String,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+    pub sha256: String,
+    // Only missing for jars that were put in place without going through the plugin endpoints.
+    pub metadata: Option<PluginMetadata>,
+}
+
+// The metadata is stored by the hash of the jar, so a jar renamed or overwritten through the file
+// endpoints never shows the metadata of another one.
+pub fn get_metadata_file_path(sha256: &str) -> String {
+    format!("{}/{}.epsilon", global::PLUGINS_DIR, sha256)
+}
+
+pub fn get_plugins(plugins_path: &str) -> Result<Vec<Plugin>, Error> {
+    let mut plugins = Vec::new();
+
+    if !Path::new(plugins_path).exists() {
+        return Ok(plugins);
+    }
+
+    let plugin_files = std::fs::read_dir(plugins_path)?
+        .filter_map(|file| file.ok())
+        .filter(|file| file.path().is_file())
+        .filter(|file| file.path().extension().is_some_and(|ext| ext == "jar"));
+
+    for file in plugin_files {
+        plugins.push(get_plugin(&file.path())?);
+    }
+
+    plugins.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    Ok(plugins)
+}
+
+pub fn get_plugin(path: &Path) -> Result<Plugin, Error> {
+    let metadata = std::fs::metadata(path)?;
+    let sha256 = files::hash_file(path)?;
+
+    Ok(Plugin {
+        file_name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        size: metadata.len(),
+        modified: DateTime::from(metadata.modified()?),
+        metadata: get_metadata(path, &sha256)?,
+        sha256,
+    })
+}
+
+// Jars without stored metadata are read once, invalid ones have none.
+fn get_metadata(jar_path: &Path, sha256: &str) -> Result<Option<PluginMetadata>, Error> {
+    let metadata_file_path_str = get_metadata_file_path(sha256);
+
+    if Path::new(&metadata_file_path_str).exists() {
+        let file = File::open(metadata_file_path_str)?;
+
+        return Ok(Some(serde_json::from_reader(&file)?));
+    }
+
+    match metadata::read_metadata(jar_path) {
+        Ok(metadata) => {
+            save_metadata(sha256, &metadata)?;
+            Ok(Some(metadata))
+        }
+        Err(err) if err.kind() == ErrorKind::InvalidData => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn save_metadata(sha256: &str, metadata: &PluginMetadata) -> Result<(), Error> {
+    let metadata_file_path_str = get_metadata_file_path(sha256);
+    let tmp_file_path_str = format!("{}.{}.tmp", metadata_file_path_str, uuid::Uuid::new_v4());
+    let tmp_file = File::create(&tmp_file_path_str)?;
+
+    serde_json::to_writer_pretty(tmp_file, metadata)?;
+
+    std::fs::rename(tmp_file_path_str, metadata_file_path_str)
+}
+
+// The upload is fully written next to the other temporary files first and checked to be a plugin,
+// then renamed into place, so the plugins directory only ever holds complete jars. The replaced
+// jar, when its name differs, is only removed once the new one is in place.
+pub async fn install(
+    file: &mut TempFile<'_>,
+    destination: &Path,
+    replaced: Option<&Path>,
+) -> Result<PluginMetadata, ApiError> {
+    if destination.extension().is_none_or(|ext| ext != "jar") {
+        return Err(ApiError::new(
+            "A plugin must be a .jar file.",
+            Status::BadRequest,
+        ));
+    }
+
+    let tmp_file_path_str = format!(
+        "{}/{}.tmp",
+        global::DATA_TMP_FILES_DIR,
+        uuid::Uuid::new_v4()
+    );
+
+    file.persist_to(&tmp_file_path_str)
+        .await
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    let result = check_and_move(Path::new(&tmp_file_path_str), destination);
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_file_path_str);
+    }
+
+    let metadata = result?;
+
+    if let Some(replaced) = replaced.filter(|replaced| *replaced != destination) {
+        remove(replaced).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    }
+
+    Ok(metadata)
+}
+
+// Removes a jar along with its metadata. Another jar with the same content gets its metadata back
+// the next time it is listed.
+pub fn remove(path: &Path) -> Result<(), Error> {
+    let sha256 = files::hash_file(path)?;
+
+    std::fs::remove_file(path)?;
+
+    remove_metadata(&sha256)
+}
+
+fn remove_metadata(sha256: &str) -> Result<(), Error> {
+    match std::fs::remove_file(get_metadata_file_path(sha256)) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+fn check_and_move(tmp_file_path: &Path, destination: &Path) -> Result<PluginMetadata, ApiError> {
+    let metadata = metadata::read_metadata(tmp_file_path).map_err(|err| match err.kind() {
+        ErrorKind::InvalidData => ApiError::new(&err.to_string(), Status::BadRequest),
+        _ => ApiError::default(err.to_string().as_str()),
+    })?;
+
+    let sha256 = files::hash_file(tmp_file_path)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    save_metadata(&sha256, &metadata).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    // The metadata of an overwritten jar goes with it, the jar itself is already replaced by then.
+    let overwritten_sha256 = match destination.is_file() {
+        true => files::hash_file(destination).ok(),
+        false => None,
+    };
+
+    std::fs::rename(tmp_file_path, destination)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    if let Some(overwritten_sha256) = overwritten_sha256.filter(|hash| *hash != sha256) {
+        let _ = remove_metadata(&overwritten_sha256);
+    }
+
+    Ok(metadata)
+}
@@ -0,0 +1,342 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read};
+use std::path::Path;
+
+use rocket::serde::json::{serde_json, Value as JsonValue};
+use rocket::serde::{Deserialize, Serialize};
+use serde_yaml::Value as YamlValue;
+use zip::ZipArchive;
+
+// Descriptors are looked up in this order, the first one found gives the metadata.
+const DESCRIPTORS: [(&str, Platform); 4] = [
+    ("paper-plugin.yml", Platform::Paper),
+    ("plugin.yml", Platform::Bukkit),
+    ("bungee.yml", Platform::Bungee),
+    ("velocity-plugin.json", Platform::Velocity),
+];
+
+// Real descriptors are a few KiB, a larger one is rejected instead of being read into memory.
+const MAX_DESCRIPTOR_SIZE: u64 = 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Paper,
+    Bukkit,
+    Bungee,
+    Velocity,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PluginMetadata {
+    pub name: String,
+    pub version: Option<String>,
+    pub authors: Vec<String>,
+    pub depend: Vec<String>,
+    pub softdepend: Vec<String>,
+    pub api_version: Option<String>,
+    pub platform: Platform,
+    // Every platform the jar has a descriptor for, some jars support servers and proxies.
+    pub platforms: Vec<Platform>,
+}
+
+pub fn read_metadata(jar_path: &Path) -> Result<PluginMetadata, Error> {
+    let jar_file = File::open(jar_path)?;
+    let mut jar_archive = ZipArchive::new(jar_file)
+        .map_err(|_| invalid_plugin("The file isn't a valid jar archive."))?;
+
+    let mut metadata: Option<PluginMetadata> = None;
+    let mut platforms = Vec::new();
+
+    for (descriptor_name, platform) in DESCRIPTORS {
+        let mut descriptor = match jar_archive.by_name(descriptor_name) {
+            Ok(descriptor) => descriptor,
+            Err(_) => continue,
+        };
+
+        platforms.push(platform);
+
+        if metadata.is_some() {
+            continue;
+        }
+
+        let mut content = String::new();
+
+        descriptor
+            .by_ref()
+            .take(MAX_DESCRIPTOR_SIZE + 1)
+            .read_to_string(&mut content)
+            .map_err(|_| invalid_plugin(&format!("The {} isn't valid UTF-8.", descriptor_name)))?;
+
+        if content.len() as u64 > MAX_DESCRIPTOR_SIZE {
+            return Err(invalid_plugin(&format!(
+                "The {} is larger than {} bytes.",
+                descriptor_name, MAX_DESCRIPTOR_SIZE
+            )));
+        }
+
+        metadata = Some(match platform {
+            Platform::Velocity => parse_velocity_descriptor(&content)?,
+            _ => parse_yaml_descriptor(&content, platform)?,
+        });
+    }
+
+    let mut metadata = metadata.ok_or_else(|| {
+        invalid_plugin(
+            "The jar has no plugin.yml, paper-plugin.yml, bungee.yml or velocity-plugin.json.",
+        )
+    })?;
+
+    metadata.platforms = platforms;
+
+    Ok(metadata)
+}
+
+fn invalid_plugin(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn parse_yaml_descriptor(content: &str, platform: Platform) -> Result<PluginMetadata, Error> {
+    let descriptor: YamlValue = serde_yaml::from_str(content)
+        .map_err(|err| invalid_plugin(&format!("The plugin descriptor is invalid: {}", err)))?;
+
+    let name = yaml_string(&descriptor["name"])
+        .ok_or_else(|| invalid_plugin("The plugin descriptor has no name."))?;
+
+    let mut authors: Vec<String> = yaml_string(&descriptor["author"]).into_iter().collect();
+    authors.extend(yaml_strings(&descriptor["authors"]));
+
+    let (depend, softdepend) = match platform {
+        // Paper plugins declare `dependencies.server.<name>.required` instead.
+        Platform::Paper => {
+            let mut depend = Vec::new();
+            let mut softdepend = Vec::new();
+
+            if let YamlValue::Mapping(dependencies) = &descriptor["dependencies"]["server"] {
+                for (dependency_name, dependency) in dependencies {
+                    if let Some(dependency_name) = yaml_string(dependency_name) {
+                        match dependency["required"].as_bool().unwrap_or(true) {
+                            true => depend.push(dependency_name),
+                            false => softdepend.push(dependency_name),
+                        }
+                    }
+                }
+            }
+
+            (depend, softdepend)
+        }
+        Platform::Bungee => (
+            yaml_strings(&descriptor["depends"]),
+            yaml_strings(&descriptor["softDepends"]),
+        ),
+        _ => (
+            yaml_strings(&descriptor["depend"]),
+            yaml_strings(&descriptor["softdepend"]),
+        ),
+    };
+
+    Ok(PluginMetadata {
+        name,
+        version: yaml_string(&descriptor["version"]),
+        authors,
+        depend,
+        softdepend,
+        api_version: yaml_string(&descriptor["api-version"]),
+        platform,
+        platforms: Vec::new(),
+    })
+}
+
+// Velocity plugins are referenced by their id, which is also what dependencies use.
+fn parse_velocity_descriptor(content: &str) -> Result<PluginMetadata, Error> {
+    let descriptor: JsonValue = serde_json::from_str(content)
+        .map_err(|err| invalid_plugin(&format!("The plugin descriptor is invalid: {}", err)))?;
+
+    let name = descriptor["id"]
+        .as_str()
+        .ok_or_else(|| invalid_plugin("The plugin descriptor has no id."))?
+        .to_string();
+
+    let authors = descriptor["authors"]
+        .as_array()
+        .map(|authors| {
+            authors
+                .iter()
+                .filter_map(|author| author.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut depend = Vec::new();
+    let mut softdepend = Vec::new();
+
+    for dependency in descriptor["dependencies"].as_array().into_iter().flatten() {
+        if let Some(dependency_id) = dependency["id"].as_str() {
+            match dependency["optional"].as_bool().unwrap_or(false) {
+                false => depend.push(dependency_id.to_string()),
+                true => softdepend.push(dependency_id.to_string()),
+            }
+        }
+    }
+
+    Ok(PluginMetadata {
+        name,
+        version: descriptor["version"].as_str().map(String::from),
+        authors,
+        depend,
+        softdepend,
+        api_version: None,
+        platform: Platform::Velocity,
+        platforms: Vec::new(),
+    })
+}
+
+// Unquoted versions like `1.20` are read as numbers by YAML.
+fn yaml_string(value: &YamlValue) -> Option<String> {
+    match value {
+        YamlValue::String(value) => Some(value.clone()),
+        YamlValue::Number(value) => Some(value.to_string()),
+        YamlValue::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+// Lists may also be given as a single value.
+fn yaml_strings(value: &YamlValue) -> Vec<String> {
+    match value {
+        YamlValue::Sequence(values) => values.iter().filter_map(yaml_string).collect(),
+        value => yaml_string(value).into_iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    use super::*;
+
+    fn read_jar(files: &[(&str, &[u8])]) -> Result<PluginMetadata, Error> {
+        let path = std::env::temp_dir().join(format!("{}.jar", uuid::Uuid::new_v4()));
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+
+        for (name, content) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+
+        zip.finish().unwrap();
+
+        let result = read_metadata(&path);
+        std::fs::remove_file(path).unwrap();
+
+        result
+    }
+
+    #[test]
+    fn parse_bukkit_descriptor() {
+        let metadata = parse_yaml_descriptor(
+            "name: Lobby\nversion: 1.2\nauthor: a\nauthors: [b, c]\ndepend: [Vault]\nsoftdepend: PlaceholderAPI\napi-version: 1.20\n",
+            Platform::Bukkit,
+        )
+        .unwrap();
+
+        assert_eq!(metadata.name, "Lobby");
+        assert_eq!(metadata.version.as_deref(), Some("1.2"));
+        assert_eq!(metadata.authors, vec!["a", "b", "c"]);
+        assert_eq!(metadata.depend, vec!["Vault"]);
+        assert_eq!(metadata.softdepend, vec!["PlaceholderAPI"]);
+        assert_eq!(metadata.api_version.as_deref(), Some("1.2"));
+    }
+
+    #[test]
+    fn parse_paper_server_dependencies() {
+        let metadata = parse_yaml_descriptor(
+            "name: Lobby\ndepend: [Ignored]\ndependencies:\n  server:\n    Vault:\n      load: BEFORE\n    LuckPerms:\n      required: true\n    PlaceholderAPI:\n      required: false\n  bootstrap:\n    Other: {}\n",
+            Platform::Paper,
+        )
+        .unwrap();
+
+        assert_eq!(metadata.depend, vec!["Vault", "LuckPerms"]);
+        assert_eq!(metadata.softdepend, vec!["PlaceholderAPI"]);
+    }
+
+    #[test]
+    fn parse_bungee_dependencies() {
+        let metadata = parse_yaml_descriptor(
+            "name: Proxy\ndepends: [LuckPerms]\nsoftDepends: [Geyser]\ndepend: [Ignored]\n",
+            Platform::Bungee,
+        )
+        .unwrap();
+
+        assert_eq!(metadata.depend, vec!["LuckPerms"]);
+        assert_eq!(metadata.softdepend, vec!["Geyser"]);
+    }
+
+    #[test]
+    fn parse_velocity_dependencies() {
+        let metadata = parse_velocity_descriptor(
+            r#"{"id": "proxy", "name": "Proxy", "version": "2.0", "authors": ["a"], "dependencies": [{"id": "luckperms"}, {"id": "geyser", "optional": true}, {"id": "viaversion", "optional": false}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(metadata.name, "proxy");
+        assert_eq!(metadata.version.as_deref(), Some("2.0"));
+        assert_eq!(metadata.authors, vec!["a"]);
+        assert_eq!(metadata.depend, vec!["luckperms", "viaversion"]);
+        assert_eq!(metadata.softdepend, vec!["geyser"]);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_descriptors() {
+        let errors = [
+            parse_yaml_descriptor("version: 1\n", Platform::Bukkit)
+                .err()
+                .unwrap(),
+            parse_yaml_descriptor("name: [a\n", Platform::Bukkit)
+                .err()
+                .unwrap(),
+            parse_velocity_descriptor(r#"{"name": "Proxy"}"#)
+                .err()
+                .unwrap(),
+            parse_velocity_descriptor("{").err().unwrap(),
+        ];
+
+        for err in errors {
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn read_metadata_prefers_the_first_descriptor() {
+        let metadata = read_jar(&[
+            ("velocity-plugin.json", br#"{"id": "both"}"#),
+            ("plugin.yml", b"name: Both\n"),
+            ("paper-plugin.yml", b"name: BothPaper\n"),
+        ])
+        .unwrap();
+
+        assert_eq!(metadata.name, "BothPaper");
+        assert!(metadata.platform == Platform::Paper);
+        assert!(metadata.platforms == vec![Platform::Paper, Platform::Bukkit, Platform::Velocity]);
+    }
+
+    #[test]
+    fn read_metadata_rejects_invalid_jars() {
+        let oversized = format!("name: a\n#{}\n", "a".repeat(MAX_DESCRIPTOR_SIZE as usize));
+        let jars: [&[(&str, &[u8])]; 3] = [
+            &[("META-INF/MANIFEST.MF", b"")],
+            &[("plugin.yml", oversized.as_bytes())],
+            &[("plugin.yml", &[0xff, 0xfe])],
+        ];
+
+        for files in jars {
+            assert_eq!(
+                read_jar(files).err().unwrap().kind(),
+                ErrorKind::InvalidData
+            );
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::Error;
+use std::path::Path;
+
+use rocket::serde::Serialize;
+
+use crate::parents;
+use crate::parents::parent::{Parent, Type};
+use crate::templates;
+use crate::templates::merge;
+use crate::templates::template::Template;
+
+use super::manager::{self, Plugin};
+use super::metadata::Platform;
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    InvalidPlugin,
+    DuplicatePlugin,
+    MissingDependency,
+    PlatformMismatch,
+    ApiVersionMismatch,
+}
+
+#[derive(Serialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub file_name: String,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub issues: Vec<Issue>,
+}
+
+pub fn validate_parent(parent: &Parent) -> Result<ValidationReport, Error> {
+    let plugins = manager::get_plugins(&parents::manager::get_parent_plugins_path(&parent.name))?;
+
+    Ok(validate(parent, plugins))
+}
+
+// The plugins are the ones of the merged parent and template trees, as they end up in the archive.
+pub fn validate_template(template: &Template) -> Result<ValidationReport, Error> {
+    let parent = parents::manager::get_parent_obj(&template.parent)?;
+    let template_path = templates::manager::get_template_path(&template.name);
+
+    let mut plugins = Vec::new();
+
+    for entry in merge::get_merged_entries(template, &template_path)? {
+        let path = Path::new(&entry.path);
+
+        let is_plugin = !entry.directory
+            && path.parent() == Some(Path::new("plugins"))
+            && path.extension().is_some_and(|ext| ext == "jar");
+
+        if is_plugin {
+            plugins.push(manager::get_plugin(&entry.source)?);
+        }
+    }
+
+    Ok(validate(&parent, plugins))
+}
+
+fn validate(parent: &Parent, plugins: Vec<Plugin>) -> ValidationReport {
+    let mut issues = Vec::new();
+    let mut file_names_by_name: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+
+    for plugin in &plugins {
+        match &plugin.metadata {
+            Some(metadata) => file_names_by_name
+                .entry(&metadata.name)
+                .or_default()
+                .push(&plugin.file_name),
+            None => issues.push(Issue {
+                kind: IssueKind::InvalidPlugin,
+                file_name: plugin.file_name.clone(),
+                message: String::from("The jar has no readable plugin descriptor."),
+            }),
+        }
+    }
+
+    for (name, file_names) in &file_names_by_name {
+        for file_name in file_names.iter().skip(1) {
+            issues.push(Issue {
+                kind: IssueKind::DuplicatePlugin,
+                file_name: file_name.to_string(),
+                message: format!("The plugin {} is also provided by {}.", name, file_names[0]),
+            });
+        }
+    }
+
+    let names: HashSet<&str> = file_names_by_name.keys().copied().collect();
+
+    for plugin in &plugins {
+        let metadata = match &plugin.metadata {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+
+        for dependency in &metadata.depend {
+            if !names.contains(dependency.as_str()) {
+                issues.push(Issue {
+                    kind: IssueKind::MissingDependency,
+                    file_name: plugin.file_name.clone(),
+                    message: format!("The plugin {} depends on {}.", metadata.name, dependency),
+                });
+            }
+        }
+
+        let supported = metadata.platforms.iter().any(|platform| {
+            matches!(
+                (&parent.t, platform),
+                (Type::Server, Platform::Paper | Platform::Bukkit)
+                    | (Type::Proxy, Platform::Bungee | Platform::Velocity)
+            )
+        });
+
+        if !supported {
+            issues.push(Issue {
+                kind: IssueKind::PlatformMismatch,
+                file_name: plugin.file_name.clone(),
+                message: format!(
+                    "The plugin {} doesn't support the {} parent type.",
+                    metadata.name,
+                    match parent.t {
+                        Type::Server => "Server",
+                        Type::Proxy => "Proxy",
+                    }
+                ),
+            });
+        }
+
+        if let Some(issue) = check_api_version(parent, plugin) {
+            issues.push(issue);
+        }
+    }
+
+    ValidationReport {
+        valid: issues.is_empty(),
+        issues,
+    }
+}
+
+// The `api-version` is the oldest server version a plugin runs on, proxies have none.
+fn check_api_version(parent: &Parent, plugin: &Plugin) -> Option<Issue> {
+    let metadata = plugin.metadata.as_ref()?;
+    let api_version = metadata.api_version.as_ref()?;
+
+    let message = match (&parent.t, &parent.minecraft_version) {
+        (Type::Proxy, _) => format!(
+            "The plugin {} requires the server API {}, the parent is a proxy.",
+            metadata.name, api_version
+        ),
+        (Type::Server, Some(minecraft_version))
+            if parse_version(api_version) > parse_version(minecraft_version) =>
+        {
+            format!(
+                "The plugin {} requires the server API {}, the parent runs {}.",
+                metadata.name, api_version, minecraft_version
+            )
+        }
+        _ => return None,
+    };
+
+    Some(Issue {
+        kind: IssueKind::ApiVersionMismatch,
+        file_name: plugin.file_name.clone(),
+        message,
+    })
+}
+
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .split('.')
+        .map_while(|part| part.trim().parse().ok())
+        .collect()
+}
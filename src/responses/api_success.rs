@@ -10,6 +10,10 @@ pub struct ApiSuccess {
 }
 
 impl ApiSuccess {
+    pub fn new(data: Value, status: Status) -> ApiSuccess {
+        ApiSuccess { json: data, status }
+    }
+
     pub fn default(message: &str) -> ApiSuccess {
         ApiSuccess {
             json: json!({ "success": message }),
@@ -23,6 +27,15 @@ impl ApiSuccess {
             status: Status::Ok,
         }
     }
+
+    // Adds a warning about something that failed once the action itself succeeded.
+    pub fn with_warning(mut self, warning: Option<String>) -> ApiSuccess {
+        if let (Some(warning), Some(object)) = (warning, self.json.as_object_mut()) {
+            object.insert(String::from("warning"), Value::String(warning));
+        }
+
+        self
+    }
 }
 
 #[rocket::async_trait]
@@ -0,0 +1,84 @@
+use std::fs::File;
+
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::Responder;
+use rocket::tokio::io::DuplexStream;
+use rocket::{response, Request, Response};
+
+pub struct IfNoneMatch(pub Option<String>);
+
+impl IfNoneMatch {
+    pub fn matches(&self, etag: &str) -> bool {
+        match &self.0 {
+            Some(header) => header
+                .split(',')
+                .map(|value| value.trim())
+                .any(|value| value == "*" || value == etag),
+            None => false,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let header = req.headers().get_one("If-None-Match").map(String::from);
+
+        Outcome::Success(IfNoneMatch(header))
+    }
+}
+
+pub enum ArchiveBody {
+    File(File),
+    Stream(DuplexStream),
+}
+
+// The map versions the archive is made of are sent along, as "name@version" separated by commas.
+pub enum CachedArchive {
+    Archive {
+        body: ArchiveBody,
+        etag: String,
+        maps: String,
+    },
+    NotModified {
+        etag: String,
+        maps: String,
+    },
+}
+
+const MAPS_HEADER: &str = "X-Epsilon-Maps";
+
+#[rocket::async_trait]
+impl<'r> Responder<'r, 'static> for CachedArchive {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        match self {
+            CachedArchive::Archive {
+                body: ArchiveBody::File(file),
+                etag,
+                maps,
+            } => Response::build_from(file.respond_to(req)?)
+                .header(ContentType::ZIP)
+                .raw_header("ETag", etag)
+                .raw_header(MAPS_HEADER, maps)
+                .ok(),
+            CachedArchive::Archive {
+                body: ArchiveBody::Stream(stream),
+                etag,
+                maps,
+            } => Response::build()
+                .header(ContentType::ZIP)
+                .raw_header("ETag", etag)
+                .raw_header(MAPS_HEADER, maps)
+                .streamed_body(stream)
+                .ok(),
+            CachedArchive::NotModified { etag, maps } => Response::build()
+                .status(Status::NotModified)
+                .raw_header("ETag", etag)
+                .raw_header(MAPS_HEADER, maps)
+                .ok(),
+        }
+    }
+}
@@ -4,3 +4,9 @@ use rocket::fs::TempFile;
 pub struct Upload<'f> {
     pub upload: TempFile<'f>,
 }
+
+#[derive(FromForm)]
+pub struct NestedUpload<'f> {
+    pub path: String,
+    pub upload: TempFile<'f>,
+}
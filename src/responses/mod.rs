@@ -1,3 +1,4 @@
 pub mod api_error;
 pub mod api_success;
+pub mod cached_archive;
 pub mod file_upload;
@@ -0,0 +1,230 @@
+use std::path::{Component, Path, PathBuf};
+
+use rocket::fs::TempFile;
+use rocket::http::Status;
+
+use crate::responses::api_error::ApiError;
+
+const MAX_NAME_LENGTH: usize = 64;
+
+const MAX_FILE_NAME_LENGTH: usize = 255;
+
+// The metadata files of parents and templates use this extension.
+const RESERVED_EXTENSION: &str = ".epsilon";
+
+// Names of parents, templates and maps end up in directory names and image tags, so they are
+// limited to a conservative set of characters. The reserved extension is left to the directories
+// stored next to them.
+pub fn validate_name(name: &str) -> Result<(), ApiError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && !name.starts_with('.')
+        && !name.ends_with(RESERVED_EXTENSION)
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    if !valid {
+        return Err(ApiError::new(
+            &format!(
+                "The name {:?} is invalid, it must be 1 to {} letters, digits, '-', '_' or '.', can't start with '.' and can't end with {}.",
+                name, MAX_NAME_LENGTH, RESERVED_EXTENSION
+            ),
+            Status::BadRequest,
+        ));
+    }
+
+    Ok(())
+}
+
+// A file name is a single path component, it can't be empty, `.`, `..` or contain a separator.
+pub fn validate_file_name(file_name: &str) -> Result<(), ApiError> {
+    let invalid_reason = if file_name.is_empty() {
+        Some("it is empty")
+    } else if file_name.len() > MAX_FILE_NAME_LENGTH {
+        Some("it is too long")
+    } else if file_name == "." || file_name == ".." {
+        Some("it refers to a directory")
+    } else if file_name.contains(['/', '\\']) {
+        Some("it contains a path separator")
+    } else if file_name.chars().any(|c| c.is_control()) {
+        Some("it contains a control character")
+    } else if file_name.ends_with(RESERVED_EXTENSION) {
+        Some("the extension is reserved")
+    } else {
+        None
+    };
+
+    match invalid_reason {
+        Some(reason) => Err(ApiError::new(
+            &format!("The file name {:?} is invalid, {}.", file_name, reason),
+            Status::BadRequest,
+        )),
+        None => Ok(()),
+    }
+}
+
+// Whether a path relative to a parent or template directory is metadata, that is one of its
+// components has the reserved extension.
+pub fn is_reserved(relative_path: &Path) -> bool {
+    relative_path.components().any(|component| {
+        component
+            .as_os_str()
+            .to_string_lossy()
+            .ends_with(RESERVED_EXTENSION)
+    })
+}
+
+// Returns the name the client gave to the uploaded file, once validated.
+pub fn get_upload_file_name(file: &TempFile<'_>) -> Result<String, ApiError> {
+    let file_name = file
+        .raw_name()
+        .map(|raw_name| raw_name.dangerous_unsafe_unsanitized_raw().as_str())
+        .ok_or_else(|| ApiError::new("The uploaded file has no name.", Status::BadRequest))?;
+
+    validate_file_name(file_name)?;
+
+    Ok(file_name.to_string())
+}
+
+// Joins a relative path to a base directory, checking every component and making sure the result,
+// symlinks included, stays inside the base directory.
+pub fn join(base_path: &str, relative_path: &str) -> Result<PathBuf, ApiError> {
+    let path = Path::new(relative_path);
+
+    if path.has_root() || relative_path.starts_with('\\') {
+        return Err(ApiError::new(
+            &format!("The path {:?} must be relative.", relative_path),
+            Status::BadRequest,
+        ));
+    }
+
+    let mut joined_path = PathBuf::from(base_path);
+
+    for component in path.components() {
+        match component {
+            Component::Normal(file_name) => {
+                let file_name = file_name.to_str().ok_or_else(|| {
+                    ApiError::new("The path isn't valid UTF-8.", Status::BadRequest)
+                })?;
+
+                validate_file_name(file_name)?;
+                joined_path.push(file_name);
+            }
+            Component::CurDir => {}
+            _ => {
+                return Err(ApiError::new(
+                    &format!("The path {:?} can't leave its directory.", relative_path),
+                    Status::BadRequest,
+                ))
+            }
+        }
+    }
+
+    if joined_path == Path::new(base_path) {
+        return Err(ApiError::new("The path is empty.", Status::BadRequest));
+    }
+
+    check_inside(base_path, &joined_path)?;
+
+    Ok(joined_path)
+}
+
+// Resolves the deepest existing ancestor of the path, which is where a symlink could lead elsewhere.
+fn check_inside(base_path: &str, path: &Path) -> Result<(), ApiError> {
+    let base_path = std::fs::canonicalize(base_path)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    let existing_path = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(path);
+
+    let existing_path = std::fs::canonicalize(existing_path)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    if !existing_path.starts_with(&base_path) {
+        return Err(ApiError::new(
+            "The path can't leave its directory.",
+            Status::BadRequest,
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_name_accepts_conservative_names() {
+        assert!(validate_name("lobby").is_ok());
+        assert!(validate_name("bed-wars_2.v1").is_ok());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LENGTH)).is_ok());
+    }
+
+    #[test]
+    fn validate_name_rejects_unsafe_names() {
+        for name in [
+            "",
+            ".hidden",
+            "a/b",
+            "a b",
+            "..",
+            "é",
+            "a\\b",
+            "a.builds.epsilon",
+        ] {
+            assert!(validate_name(name).is_err(), "{:?}", name);
+        }
+
+        assert!(validate_name(&"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn validate_file_name_rejects_unsafe_file_names() {
+        assert!(validate_file_name("server.properties").is_ok());
+        assert!(validate_file_name(".env").is_ok());
+
+        for file_name in ["", ".", "..", "a/b", "a\\b", "a\nb", "details.epsilon"] {
+            assert!(validate_file_name(file_name).is_err(), "{:?}", file_name);
+        }
+
+        assert!(validate_file_name(&"a".repeat(MAX_FILE_NAME_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn is_reserved_checks_every_component() {
+        assert!(is_reserved(Path::new("details.epsilon")));
+        assert!(is_reserved(Path::new("builds.epsilon/1.log")));
+        assert!(!is_reserved(Path::new("plugins/epsilon.jar")));
+    }
+
+    #[test]
+    fn join_stays_inside_the_base_path() {
+        let base_path = std::env::temp_dir();
+        let base_path_str = base_path.to_str().unwrap();
+
+        assert_eq!(
+            join(base_path_str, "./plugins/a.jar").unwrap(),
+            base_path.join("plugins/a.jar")
+        );
+
+        for relative_path in [
+            "",
+            ".",
+            "/etc/passwd",
+            "\\etc",
+            "../a",
+            "a/../../b",
+            "a//../b",
+        ] {
+            assert!(
+                join(base_path_str, relative_path).is_err(),
+                "{:?}",
+                relative_path
+            );
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind, Write};
+use std::path::Path;
+
+use rocket::tokio;
+use sha2::{Digest, Sha256};
+use tokio_util::io::SyncIoBridge;
+use zip::ZipArchive;
+
+use crate::responses::cached_archive::ArchiveBody;
+
+use crate::maps::reference::ResolvedMap;
+use crate::templates::template::Template;
+use crate::{global, maps, parents};
+
+use super::variables::{self, Variables};
+use super::{manager, merge, utils};
+
+// Part of every key, to be bumped whenever the archive layout changes.
+const ARCHIVE_FORMAT_VERSION: u32 = 8;
+
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+pub fn get_cache_path(name: &str) -> String {
+    format!("{}/{}", global::CACHE_DIR, name)
+}
+
+// The live template and each of its snapshots have their own slot, holding the archive of the
+// latest key only.
+fn get_cache_slot(version: Option<u32>) -> String {
+    match version {
+        Some(version) => version.to_string(),
+        None => String::from("latest"),
+    }
+}
+
+pub fn get_cached_archive_path(name: &str, version: Option<u32>, key: &str) -> String {
+    format!(
+        "{}/{}.{}.zip",
+        get_cache_path(name),
+        get_cache_slot(version),
+        key
+    )
+}
+
+// Every input of the archive is fingerprinted by its path, size and modification time, so a push
+// to the parent, the template or a new version of a map it follows gives a new key.
+pub fn get_archive_key(
+    template: &Template,
+    template_path: &str,
+    resolved_maps: &[ResolvedMap],
+) -> Result<String, Error> {
+    let parent_path = parents::manager::get_parent_path(&template.parent);
+    let mut hasher = Sha256::new();
+
+    hasher.update(format!("format:{}\n", ARCHIVE_FORMAT_VERSION));
+
+    hash_dir_metadata(&mut hasher, "parent", &parent_path)?;
+    hash_dir_metadata(&mut hasher, "template", template_path)?;
+
+    for resolved_map in resolved_maps {
+        let version_path_str =
+            maps::manager::get_version_path(&resolved_map.name, resolved_map.version);
+
+        hash_file_metadata(
+            &mut hasher,
+            &format!("map/{}", resolved_map),
+            Path::new(&version_path_str),
+        )?;
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn hash_dir_metadata(hasher: &mut Sha256, layer: &str, dir_path: &str) -> Result<(), Error> {
+    let paths = glob::glob(&format!("{}/**/*", dir_path)).map_err(Error::other)?;
+
+    for glob_result in paths {
+        let path = glob_result.map_err(Error::other)?;
+        let relative_path = utils::strip_base_path(&path, dir_path)?;
+
+        hash_file_metadata(
+            hasher,
+            &format!("{}/{}", layer, relative_path.to_string_lossy()),
+            &path,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn hash_file_metadata(hasher: &mut Sha256, name: &str, path: &Path) -> Result<(), Error> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(Error::other)?;
+
+    hasher.update(format!(
+        "{}:{}:{}:{}\n",
+        name,
+        metadata.len(),
+        modified.as_secs(),
+        modified.subsec_nanos()
+    ));
+
+    Ok(())
+}
+
+// On a miss, the archive is streamed to the response while being written to a file of its own,
+// which is renamed into the cache once complete. Concurrent requests never share a file and the
+// memory used is bounded by the pipe size.
+pub fn get_archive(
+    template: Template,
+    template_path: String,
+    version: Option<u32>,
+    resolved_maps: Vec<ResolvedMap>,
+    key: &str,
+) -> Result<ArchiveBody, Error> {
+    let name = template.name.clone();
+    let cached_archive_path_str = get_cached_archive_path(&name, version, key);
+
+    match File::open(&cached_archive_path_str) {
+        Ok(file) => return Ok(ArchiveBody::File(file)),
+        Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+        Err(_) => {}
+    }
+
+    // Unresolved variables, invalid patches and unreadable maps are reported before anything is
+    // sent, once the response started a failure can only cut the archive short.
+    let entries = merge::get_merged_entries(&template, &template_path)?;
+
+    variables::check_entries(&entries, &Variables::new(&template))?;
+
+    for resolved_map in &resolved_maps {
+        let version_path_str =
+            maps::manager::get_version_path(&resolved_map.name, resolved_map.version);
+
+        ZipArchive::new(File::open(version_path_str)?)?;
+    }
+
+    std::fs::create_dir_all(get_cache_path(&name))?;
+
+    let tmp_archive_path_str = format!(
+        "{}/{}.{}.{}.tmp",
+        get_cache_path(&name),
+        get_cache_slot(version),
+        key,
+        uuid::Uuid::new_v4()
+    );
+
+    let cache_file = File::create(&tmp_archive_path_str)?;
+    let (reader, writer) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+    let response = SyncIoBridge::new(writer);
+
+    tokio::task::spawn_blocking(move || {
+        let writer = TeeWriter {
+            response,
+            cache: Some(cache_file),
+        };
+
+        let result = utils::write_template_zip(writer, &template, &template_path, &resolved_maps);
+
+        let cached = match result {
+            Ok(writer) => writer
+                .cache
+                .map(|file| file.sync_all().is_ok())
+                .unwrap_or(false),
+            Err(err) => {
+                error!("Failed to stream the archive of {}: {}", name, err);
+                false
+            }
+        };
+
+        if !cached || std::fs::rename(&tmp_archive_path_str, &cached_archive_path_str).is_err() {
+            let _ = std::fs::remove_file(&tmp_archive_path_str);
+        } else if let Err(err) = remove_superseded(&name, version, &cached_archive_path_str) {
+            error!("Failed to clean up the archive cache of {}: {}", name, err);
+        }
+    });
+
+    Ok(ArchiveBody::Stream(reader))
+}
+
+// Writes to the response and, while it keeps working, to the cache file. A failing cache only
+// costs the caching, while a failing response (the client went away) stops the archive.
+struct TeeWriter<R: Write, C: Write> {
+    response: R,
+    cache: Option<C>,
+}
+
+impl<R: Write, C: Write> Write for TeeWriter<R, C> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.response.write(buf)?;
+
+        if let Some(cache) = &mut self.cache {
+            if cache.write_all(&buf[..written]).is_err() {
+                self.cache = None;
+            }
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.response.flush()?;
+
+        if let Some(cache) = &mut self.cache {
+            if cache.flush().is_err() {
+                self.cache = None;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Removes the archives of the slot with an older key, they can't be served anymore.
+fn remove_superseded(name: &str, version: Option<u32>, kept_path: &str) -> Result<(), Error> {
+    let slot_prefix = format!("{}.", get_cache_slot(version));
+
+    let superseded_files = std::fs::read_dir(get_cache_path(name))?
+        .filter_map(|file| file.ok())
+        .filter(|file| {
+            let file_name = file.file_name().to_string_lossy().to_string();
+
+            file_name.starts_with(&slot_prefix) && file_name.ends_with(".zip")
+        })
+        .filter(|file| file.path() != Path::new(kept_path));
+
+    for file in superseded_files {
+        std::fs::remove_file(file.path())?;
+    }
+
+    Ok(())
+}
+
+// Archives being written are left alone, they are renamed into place when complete.
+pub fn invalidate_template(name: &str) -> Result<(), Error> {
+    let cache_path_str = get_cache_path(name);
+
+    if !Path::new(&cache_path_str).exists() {
+        return Ok(());
+    }
+
+    let archive_files = std::fs::read_dir(cache_path_str)?
+        .filter_map(|file| file.ok())
+        .filter(|file| file.path().extension().is_some_and(|ext| ext == "zip"));
+
+    for file in archive_files {
+        std::fs::remove_file(file.path())?;
+    }
+
+    Ok(())
+}
+
+pub fn invalidate_parent(parent_name: &str) -> Result<(), Error> {
+    for template in manager::get_templates()? {
+        if template.parent == parent_name {
+            invalidate_template(&template.name)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn invalidate_map(map_name: &str) -> Result<(), Error> {
+    for template in manager::get_templates()? {
+        if template.uses_map(map_name) {
+            invalidate_template(&template.name)?;
+        }
+    }
+
+    Ok(())
+}
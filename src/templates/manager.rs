@@ -20,8 +20,7 @@ pub fn get_templates() -> Result<Vec<Template>, Error> {
         let directory_name = directory_name_os_str.to_str().unwrap();
         let current_template_result = get_template_obj(directory_name);
 
-        if let Ok(..) = current_template_result {
-            let mut current_template = current_template_result.unwrap();
+        if let Ok(mut current_template) = current_template_result {
             let current_template_parent = get_template_parent_obj(&current_template)?;
 
             current_template.t = Some(current_template_parent.t);
@@ -0,0 +1,346 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+use rocket::serde::Serialize;
+
+use crate::{parents, safe_path};
+
+use super::patch;
+use super::template::Template;
+use super::utils;
+
+// A template file named `.wh.<name>` removes `<name>` of the parent, file or directory.
+pub const WHITEOUT_PREFIX: &str = ".wh.";
+
+// A template directory holding this file hides everything the parent has in that directory.
+pub const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Layer {
+    Parent,
+    Template,
+    // A template patch merged onto the file below it, if any.
+    Patched,
+}
+
+#[derive(Serialize)]
+pub struct MergedEntry {
+    pub path: String,
+    #[serde(skip)]
+    pub source: PathBuf,
+    // The file a patch is merged onto.
+    #[serde(skip)]
+    pub base: Option<PathBuf>,
+    pub directory: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    pub layer: Layer,
+}
+
+// Merges the parent and template trees, the template overriding the parent: a template file hides
+// the parent entry at the same path and everything below it, whiteout markers hide parent entries
+// without replacing them and are not part of the result, patches are merged onto the entry they
+// apply to.
+pub fn get_merged_entries(
+    template: &Template,
+    template_path: &str,
+) -> Result<Vec<MergedEntry>, Error> {
+    merge_entries(
+        &parents::manager::get_parent_path(&template.parent),
+        template_path,
+    )
+}
+
+fn merge_entries(parent_path: &str, template_path: &str) -> Result<Vec<MergedEntry>, Error> {
+    let mut hidden_paths = HashSet::new();
+    let mut opaque_dirs = HashSet::new();
+    let mut template_entries = Vec::new();
+    let mut patch_entries = Vec::new();
+
+    for entry in get_entries(template_path, Layer::Template)? {
+        let path = Path::new(&entry.path);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        if file_name == OPAQUE_WHITEOUT {
+            opaque_dirs.insert(dir.to_path_buf());
+        } else if let Some(hidden_name) = file_name.strip_prefix(WHITEOUT_PREFIX) {
+            hidden_paths.insert(dir.join(hidden_name));
+        } else if !entry.directory && patch::get_patched_path(path).is_some() {
+            patch_entries.push(entry);
+        } else {
+            if !entry.directory {
+                hidden_paths.insert(path.to_path_buf());
+            }
+
+            template_entries.push(entry);
+        }
+    }
+
+    let mut merged_entries = BTreeMap::new();
+
+    for entry in get_entries(parent_path, Layer::Parent)? {
+        let path = Path::new(&entry.path);
+
+        let hidden = path
+            .ancestors()
+            .any(|ancestor| hidden_paths.contains(ancestor))
+            || path
+                .ancestors()
+                .skip(1)
+                .any(|ancestor| opaque_dirs.contains(ancestor));
+
+        if !hidden {
+            merged_entries.insert(entry.path.clone(), entry);
+        }
+    }
+
+    for entry in template_entries {
+        merged_entries.insert(entry.path.clone(), entry);
+    }
+
+    for entry in patch_entries {
+        let patched_path = patch::get_patched_path(Path::new(&entry.path))
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+
+        let base = match merged_entries.get(&patched_path) {
+            Some(base_entry) if base_entry.directory => continue,
+            Some(base_entry) => Some(base_entry.source.clone()),
+            None => None,
+        };
+
+        merged_entries.insert(
+            patched_path.clone(),
+            MergedEntry {
+                path: patched_path,
+                source: entry.source,
+                base,
+                directory: false,
+                size: None,
+                layer: Layer::Patched,
+            },
+        );
+    }
+
+    Ok(merged_entries.into_values().collect())
+}
+
+fn get_entries(dir_path: &str, layer: Layer) -> Result<Vec<MergedEntry>, Error> {
+    let mut entries = Vec::new();
+    let paths = glob::glob(&format!("{}/**/*", dir_path)).map_err(Error::other)?;
+
+    for glob_result in paths {
+        let path = glob_result.map_err(Error::other)?;
+        let relative_path = utils::strip_base_path(&path, dir_path)?;
+
+        // Details and Dockerfiles aren't content.
+        if safe_path::is_reserved(relative_path) {
+            continue;
+        }
+
+        let metadata = std::fs::metadata(&path)?;
+
+        if !metadata.is_dir() && !metadata.is_file() {
+            continue;
+        }
+
+        entries.push(MergedEntry {
+            path: relative_path.to_string_lossy().to_string(),
+            directory: metadata.is_dir(),
+            size: metadata.is_file().then_some(metadata.len()),
+            source: path,
+            base: None,
+            layer,
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Layers {
+        path: PathBuf,
+    }
+
+    impl Layers {
+        fn new() -> Layers {
+            let path = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+
+            std::fs::create_dir_all(path.join("parent")).unwrap();
+            std::fs::create_dir_all(path.join("template")).unwrap();
+
+            Layers { path }
+        }
+
+        fn write(&self, layer: &str, relative_path: &str) -> &Layers {
+            let path = self.path.join(layer).join(relative_path);
+
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, relative_path).unwrap();
+
+            self
+        }
+
+        fn layer_path(&self, layer: &str) -> String {
+            self.path.join(layer).to_string_lossy().to_string()
+        }
+
+        fn merge(&self) -> Vec<MergedEntry> {
+            merge_entries(&self.layer_path("parent"), &self.layer_path("template")).unwrap()
+        }
+
+        fn merge_summary(&self) -> Vec<(String, Layer, bool)> {
+            self.merge()
+                .into_iter()
+                .map(|entry| (entry.path, entry.layer, entry.directory))
+                .collect()
+        }
+    }
+
+    impl Drop for Layers {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+
+    fn file(path: &str, layer: Layer) -> (String, Layer, bool) {
+        (path.to_string(), layer, false)
+    }
+
+    fn dir(path: &str, layer: Layer) -> (String, Layer, bool) {
+        (path.to_string(), layer, true)
+    }
+
+    #[test]
+    fn template_files_override_parent_files() {
+        let layers = Layers::new();
+
+        layers
+            .write("parent", "a.yml")
+            .write("parent", "b.yml")
+            .write("parent", "details.epsilon")
+            .write("template", "b.yml")
+            .write("template", "details.epsilon");
+
+        assert_eq!(
+            layers.merge_summary(),
+            vec![file("a.yml", Layer::Parent), file("b.yml", Layer::Template)]
+        );
+        assert!(layers.merge()[1]
+            .source
+            .starts_with(layers.layer_path("template")));
+    }
+
+    #[test]
+    fn whiteouts_hide_parent_entries() {
+        let layers = Layers::new();
+
+        layers
+            .write("parent", "a.yml")
+            .write("parent", "plugins/a.jar")
+            .write("parent", "plugins/a/config.yml")
+            .write("parent", "plugins/b.jar")
+            .write("template", ".wh.a.yml")
+            .write("template", "plugins/.wh.a.jar")
+            .write("template", "plugins/.wh.a");
+
+        assert_eq!(
+            layers.merge_summary(),
+            vec![
+                dir("plugins", Layer::Template),
+                file("plugins/b.jar", Layer::Parent)
+            ]
+        );
+    }
+
+    #[test]
+    fn opaque_whiteouts_hide_the_parent_directory_content() {
+        let layers = Layers::new();
+
+        layers
+            .write("parent", "config/a.yml")
+            .write("parent", "config/sub/b.yml")
+            .write("parent", "other.yml")
+            .write("template", "config/.wh..wh..opq")
+            .write("template", "config/c.yml");
+
+        assert_eq!(
+            layers.merge_summary(),
+            vec![
+                dir("config", Layer::Template),
+                file("config/c.yml", Layer::Template),
+                file("other.yml", Layer::Parent)
+            ]
+        );
+    }
+
+    #[test]
+    fn template_files_hide_parent_directories() {
+        let layers = Layers::new();
+
+        layers
+            .write("parent", "world/level.dat")
+            .write("parent", "world/region/r.0.0.mca")
+            .write("template", "world");
+
+        assert_eq!(layers.merge_summary(), vec![file("world", Layer::Template)]);
+    }
+
+    #[test]
+    fn patches_are_merged_onto_the_entry_below() {
+        let layers = Layers::new();
+
+        layers
+            .write("parent", "server.properties")
+            .write("parent", "config/paper.yml/keep")
+            .write("template", "server.properties.patch")
+            .write("template", "bukkit.yml")
+            .write("template", "bukkit.yml.patch")
+            .write("template", "new.json.patch")
+            .write("template", "config/paper.yml.patch")
+            .write("template", "notes.txt.patch");
+
+        let entries = layers.merge();
+        let summary: Vec<(String, Layer, bool)> = entries
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.layer, entry.directory))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                file("bukkit.yml", Layer::Patched),
+                dir("config", Layer::Template),
+                dir("config/paper.yml", Layer::Parent),
+                file("config/paper.yml/keep", Layer::Parent),
+                file("new.json", Layer::Patched),
+                file("notes.txt.patch", Layer::Template),
+                file("server.properties", Layer::Patched),
+            ]
+        );
+
+        let base = |path: &str| {
+            entries
+                .iter()
+                .find(|entry| entry.path == path)
+                .and_then(|entry| entry.base.clone())
+        };
+
+        assert_eq!(
+            base("bukkit.yml"),
+            Some(Path::new(&layers.layer_path("template")).join("bukkit.yml"))
+        );
+        assert_eq!(base("new.json"), None);
+        assert_eq!(
+            base("server.properties"),
+            Some(Path::new(&layers.layer_path("parent")).join("server.properties"))
+        );
+    }
+}
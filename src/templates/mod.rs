@@ -1,5 +1,10 @@
+pub mod cache;
 pub mod manager;
+pub mod merge;
+pub mod patch;
 pub mod resources;
 pub mod routes;
+pub mod snapshots;
 pub mod template;
 pub mod utils;
+pub mod variables;
@@ -0,0 +1,338 @@
+use std::collections::HashSet;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use rocket::serde::json::{serde_json, Value as JsonValue};
+use serde_yaml::Value as YamlValue;
+use toml::Value as TomlValue;
+
+// A template file named `<file>.patch` is merged onto `<file>` instead of replacing it.
+pub const PATCH_EXTENSION: &str = "patch";
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Properties,
+    Yaml,
+    Json,
+    Toml,
+}
+
+// Returns the path the patch applies to, when the file is a patch of a supported format.
+pub fn get_patched_path(path: &Path) -> Option<&Path> {
+    if path.extension().is_none_or(|ext| ext != PATCH_EXTENSION) {
+        return None;
+    }
+
+    let patched_path = Path::new(path.to_str()?.strip_suffix(".patch")?);
+
+    get_format(patched_path).map(|_| patched_path)
+}
+
+pub fn get_format(path: &Path) -> Option<Format> {
+    match path.extension()?.to_str()? {
+        "properties" => Some(Format::Properties),
+        "yml" | "yaml" => Some(Format::Yaml),
+        "json" => Some(Format::Json),
+        "toml" => Some(Format::Toml),
+        _ => None,
+    }
+}
+
+// Checks a patch can be read, so that mistakes are reported on push rather than when an archive is
+// being sent.
+pub fn check(patch_path: &Path) -> Result<(), Error> {
+    let patched_path = get_patched_path(patch_path)
+        .ok_or_else(|| invalid_data(String::from("The file isn't a supported patch.")))?;
+    let format = get_format(patched_path).unwrap();
+    let patch = std::fs::read_to_string(patch_path)?;
+
+    match format {
+        Format::Properties => Ok(()),
+        Format::Yaml => parse_yaml(&patch).map(|_| ()),
+        Format::Json => parse_json(&patch).map(|_| ()),
+        Format::Toml => parse_toml(&patch).map(|_| ()),
+    }
+}
+
+// Maps are merged key by key, any other value of the patch replaces the base one. A null in a YAML
+// or JSON patch removes the key. YAML, JSON and TOML files are parsed and written back, which drops
+// the comments, the formatting and the key order of the base file, only properties files keep them.
+pub fn apply(base_path: Option<&Path>, patch_path: &Path) -> Result<Vec<u8>, Error> {
+    let patched_path = get_patched_path(patch_path)
+        .ok_or_else(|| invalid_data(String::from("The file isn't a supported patch.")))?;
+    let format = get_format(patched_path).unwrap();
+
+    let base = match base_path {
+        Some(base_path) => std::fs::read_to_string(base_path)?,
+        None => String::new(),
+    };
+    let patch = std::fs::read_to_string(patch_path)?;
+
+    let content = match format {
+        Format::Properties => merge_properties(&base, &patch),
+        Format::Yaml => {
+            let mut value = parse_yaml(&base)?;
+
+            merge_yaml(&mut value, parse_yaml(&patch)?);
+            serde_yaml::to_string(&value).map_err(Error::other)?
+        }
+        Format::Json => {
+            let mut value = parse_json(&base)?;
+
+            merge_json(&mut value, parse_json(&patch)?);
+            serde_json::to_string_pretty(&value)? + "\n"
+        }
+        Format::Toml => {
+            let mut value = parse_toml(&base)?;
+
+            merge_toml(&mut value, parse_toml(&patch)?);
+            toml::to_string_pretty(&value).map_err(Error::other)?
+        }
+    };
+
+    Ok(content.into_bytes())
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+// An empty file is an empty document.
+fn parse_yaml(content: &str) -> Result<YamlValue, Error> {
+    match serde_yaml::from_str(content) {
+        Ok(YamlValue::Null) => Ok(YamlValue::Mapping(Default::default())),
+        Ok(value) => Ok(value),
+        Err(err) => Err(invalid_data(format!("Invalid YAML: {}", err))),
+    }
+}
+
+fn parse_json(content: &str) -> Result<JsonValue, Error> {
+    if content.trim().is_empty() {
+        return Ok(JsonValue::Object(Default::default()));
+    }
+
+    serde_json::from_str(content).map_err(|err| invalid_data(format!("Invalid JSON: {}", err)))
+}
+
+fn parse_toml(content: &str) -> Result<TomlValue, Error> {
+    toml::from_str(content).map_err(|err| invalid_data(format!("Invalid TOML: {}", err)))
+}
+
+fn merge_yaml(base: &mut YamlValue, patch: YamlValue) {
+    match (base, patch) {
+        (YamlValue::Mapping(base), YamlValue::Mapping(patch)) => {
+            for (key, value) in patch {
+                match (base.get_mut(&key), value) {
+                    (_, YamlValue::Null) => {
+                        base.remove(&key);
+                    }
+                    (Some(base_value), value) => merge_yaml(base_value, value),
+                    // Merged onto an empty map so that its own nulls are dropped.
+                    (None, YamlValue::Mapping(value)) => {
+                        let mut new_value = YamlValue::Mapping(Default::default());
+
+                        merge_yaml(&mut new_value, YamlValue::Mapping(value));
+                        base.insert(key, new_value);
+                    }
+                    (None, value) => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
+
+fn merge_json(base: &mut JsonValue, patch: JsonValue) {
+    match (base, patch) {
+        (JsonValue::Object(base), JsonValue::Object(patch)) => {
+            for (key, value) in patch {
+                match (base.get_mut(&key), value) {
+                    (_, JsonValue::Null) => {
+                        base.remove(&key);
+                    }
+                    (Some(base_value), value) => merge_json(base_value, value),
+                    (None, JsonValue::Object(value)) => {
+                        let mut new_value = JsonValue::Object(Default::default());
+
+                        merge_json(&mut new_value, JsonValue::Object(value));
+                        base.insert(key, new_value);
+                    }
+                    (None, value) => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
+
+fn merge_toml(base: &mut TomlValue, patch: TomlValue) {
+    match (base, patch) {
+        (TomlValue::Table(base), TomlValue::Table(patch)) => {
+            for (key, value) in patch {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_toml(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
+
+// The base lines are kept as they are, comments and order included, only the values of the keys
+// present in the patch change. Keys the base doesn't have are appended.
+fn merge_properties(base: &str, patch: &str) -> String {
+    let mut patch_entries: Vec<(&str, &str)> = Vec::new();
+
+    for (key, value) in patch.lines().filter_map(parse_property) {
+        match patch_entries
+            .iter_mut()
+            .find(|(patch_key, _)| *patch_key == key)
+        {
+            Some(patch_entry) => patch_entry.1 = value,
+            None => patch_entries.push((key, value)),
+        }
+    }
+
+    let mut applied_keys = HashSet::new();
+    let mut lines = Vec::new();
+
+    for line in base.lines() {
+        let patch_entry = parse_property(line).and_then(|(key, _)| {
+            patch_entries
+                .iter()
+                .find(|(patch_key, _)| *patch_key == key)
+        });
+
+        match patch_entry {
+            Some((key, value)) => {
+                applied_keys.insert(*key);
+                lines.push(format!("{}={}", key, value));
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+
+    for (key, value) in &patch_entries {
+        if !applied_keys.contains(key) {
+            lines.push(format!("{}={}", key, value));
+        }
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+
+    content
+}
+
+fn parse_property(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+
+    if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+        return None;
+    }
+
+    let separator = line.find(['=', ':'])?;
+
+    Some((line[..separator].trim(), line[separator + 1..].trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_patched_path_only_supports_known_formats() {
+        assert_eq!(
+            get_patched_path(Path::new("config/paper.yml.patch")),
+            Some(Path::new("config/paper.yml"))
+        );
+        assert_eq!(get_patched_path(Path::new("plugin.jar.patch")), None);
+        assert_eq!(get_patched_path(Path::new("paper.yml")), None);
+    }
+
+    #[test]
+    fn merge_properties_keeps_the_base_lines() {
+        let base = "# Server\nmotd=Hello\nmax-players = 20\n\npvp=true\n";
+        let patch = "max-players=100\nspawn-protection:0\nmotd=First\nmotd=Second\n";
+
+        assert_eq!(
+            merge_properties(base, patch),
+            "# Server\nmotd=Second\nmax-players=100\n\npvp=true\nspawn-protection=0\n"
+        );
+    }
+
+    #[test]
+    fn merge_yaml_merges_maps_and_removes_nulls() {
+        let mut base = parse_yaml("a:\n  b: 1\n  c: [1, 2]\nd: x\n").unwrap();
+        let patch = parse_yaml("a:\n  c: [3]\n  e: 4\nd: null\nf:\n  g: null\n  h: 5\n").unwrap();
+
+        merge_yaml(&mut base, patch);
+
+        assert_eq!(
+            base,
+            parse_yaml("a:\n  b: 1\n  c: [3]\n  e: 4\nf:\n  h: 5\n").unwrap()
+        );
+    }
+
+    #[test]
+    fn merge_json_merges_objects_and_removes_nulls() {
+        let mut base = parse_json(r#"{"a": {"b": 1, "c": 2}, "d": [1]}"#).unwrap();
+        let patch = parse_json(r#"{"a": {"c": null}, "d": [2], "e": {"f": null}}"#).unwrap();
+
+        merge_json(&mut base, patch);
+
+        assert_eq!(
+            base,
+            parse_json(r#"{"a": {"b": 1}, "d": [2], "e": {}}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn merge_toml_merges_tables() {
+        let mut base = parse_toml("a = 1\n[b]\nc = \"x\"\nd = 2\n").unwrap();
+        let patch = parse_toml("[b]\nd = 3\n[e]\nf = true\n").unwrap();
+
+        merge_toml(&mut base, patch);
+
+        assert_eq!(
+            base,
+            parse_toml("a = 1\n[b]\nc = \"x\"\nd = 3\n[e]\nf = true\n").unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_documents_are_empty_maps() {
+        assert_eq!(
+            parse_yaml("").unwrap(),
+            YamlValue::Mapping(Default::default())
+        );
+        assert_eq!(
+            parse_json(" \n").unwrap(),
+            JsonValue::Object(Default::default())
+        );
+        assert_eq!(
+            parse_toml("").unwrap(),
+            TomlValue::Table(Default::default())
+        );
+    }
+
+    #[test]
+    fn malformed_documents_are_invalid_data() {
+        let errors = [
+            parse_yaml("a: [1").unwrap_err(),
+            parse_json("{\"a\": ").unwrap_err(),
+            parse_toml("a = ").unwrap_err(),
+        ];
+
+        for err in errors {
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
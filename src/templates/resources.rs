@@ -1,6 +1,9 @@
 use rocket::serde::Deserialize;
 use rocket::serde::Serialize;
 
+// Share of the container memory given to the JVM heap, the rest is left to the off-heap memory.
+const HEAP_RAM_PERCENTAGE: u64 = 75;
+
 #[derive(Serialize, Deserialize)]
 pub struct Resources {
     pub minimum: ResourcesInfo,
@@ -12,3 +15,89 @@ pub struct ResourcesInfo {
     pub cpu: f32,
     pub ram: u32,
 }
+
+impl Resources {
+    // The JVM refuses a heap of 0 and a cpu limit has to be a positive number.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        for resources_info in [&self.minimum, &self.maximum] {
+            if !(resources_info.cpu.is_finite() && resources_info.cpu > 0.0) {
+                return Err("The cpu must be a positive number.");
+            }
+
+            if resources_info.ram == 0 {
+                return Err("The ram must be a positive number.");
+            }
+        }
+
+        if self.minimum.cpu > self.maximum.cpu {
+            return Err("The minimum cpu exceeds the maximum cpu.");
+        }
+
+        if self.minimum.ram > self.maximum.ram {
+            return Err("The minimum ram exceeds the maximum ram.");
+        }
+
+        Ok(())
+    }
+}
+
+impl ResourcesInfo {
+    pub fn get_heap_size(&self) -> String {
+        // Computed on 64 bits, the product of a large ram doesn't fit in 32 bits.
+        format!("{}M", u64::from(self.ram) * HEAP_RAM_PERCENTAGE / 100)
+    }
+
+    pub fn get_memory_quantity(&self) -> String {
+        format!("{}Mi", self.ram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resources(minimum: (f32, u32), maximum: (f32, u32)) -> Resources {
+        Resources {
+            minimum: ResourcesInfo {
+                cpu: minimum.0,
+                ram: minimum.1,
+            },
+            maximum: ResourcesInfo {
+                cpu: maximum.0,
+                ram: maximum.1,
+            },
+        }
+    }
+
+    #[test]
+    fn validate_accepts_ordered_resources() {
+        assert!(resources((0.5, 512), (2.0, 1024)).validate().is_ok());
+        assert!(resources((1.0, 1024), (1.0, 1024)).validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_invalid_resources() {
+        for (minimum, maximum) in [
+            ((1.0, 0), (1.0, 1024)),
+            ((1.0, 512), (1.0, 0)),
+            ((0.0, 512), (1.0, 1024)),
+            ((-1.0, 512), (1.0, 1024)),
+            ((f32::NAN, 512), (1.0, 1024)),
+            ((1.0, 512), (f32::INFINITY, 1024)),
+            ((2.0, 512), (1.0, 1024)),
+            ((1.0, 2048), (1.0, 1024)),
+        ] {
+            assert!(resources(minimum, maximum).validate().is_err());
+        }
+    }
+
+    #[test]
+    fn get_heap_size_does_not_overflow() {
+        let resources_info = ResourcesInfo {
+            cpu: 1.0,
+            ram: u32::MAX,
+        };
+
+        assert_eq!(resources_info.get_heap_size(), "3221225471M");
+    }
+}
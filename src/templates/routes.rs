@@ -1,26 +1,123 @@
 use rocket::form::Form;
+use rocket::fs::TempFile;
 use rocket::http::Status;
 use rocket::serde::json::serde_json::json;
 use rocket::serde::json::{serde_json, Json};
 use rocket::State;
+use std::collections::HashSet;
 use std::fs::File;
+use std::io::ErrorKind;
 use std::path::Path;
-use zip::ZipWriter;
 
+use crate::auth::guards::{Admin, Editor, ReadOnly};
+use crate::builds::build::Mode;
+use crate::builds::manager::BuildQueue;
+use crate::maps::reference::{format_resolved_maps, MapReference};
 use crate::responses::api_error::ApiError;
 use crate::responses::api_success::ApiSuccess;
-use crate::responses::file_upload::Upload;
+use crate::responses::cached_archive::{CachedArchive, IfNoneMatch};
+use crate::responses::file_upload::{NestedUpload, Upload};
+use crate::safe_path;
 use crate::templates::template::Template;
-use crate::{global, parents, Config, maps};
+use crate::{builds, files, global, maps, parents, plugins};
 
-use super::{manager, utils};
+use super::{cache, manager, merge, patch, snapshots};
 
 fn init_dirs(name: &str) -> std::io::Result<()> {
     std::fs::create_dir_all(manager::get_template_plugins_path(name))
 }
 
+fn validate_template_names(template: &Template) -> Result<(), ApiError> {
+    safe_path::validate_name(&template.name)?;
+    safe_path::validate_name(&template.parent)?;
+
+    // Every map is extracted in a folder named after it, so two versions of one can't be used.
+    let mut map_names = HashSet::new();
+
+    for reference in &template.maps {
+        let reference: MapReference = reference
+            .parse()
+            .map_err(|err| ApiError::new(err, Status::BadRequest))?;
+
+        safe_path::validate_name(&reference.name)?;
+
+        if !map_names.insert(reference.name) {
+            return Err(ApiError::new(
+                "A map can only be referenced once.",
+                Status::BadRequest,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_maps_exist(template: &Template) -> Result<(), ApiError> {
+    maps::manager::resolve_all(&template.maps).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => ApiError::new(&err.to_string(), Status::BadRequest),
+        _ => ApiError::default(err.to_string().as_str()),
+    })?;
+
+    Ok(())
+}
+
+// A patch is written next to the other temporary files and checked before taking the place of the
+// previous one, a patch that can't be read would otherwise break every archive.
+async fn persist_upload(file: &mut TempFile<'_>, destination: &Path) -> Result<(), ApiError> {
+    let file_name = match patch::get_patched_path(destination) {
+        Some(_) => destination
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy(),
+        None => {
+            return file
+                .persist_to(destination)
+                .await
+                .map_err(|err| ApiError::default(err.to_string().as_str()))
+        }
+    };
+
+    // The name is kept as the format of the patch is given by its extensions.
+    let tmp_file_path_str = format!(
+        "{}/{}.{}",
+        global::DATA_TMP_FILES_DIR,
+        uuid::Uuid::new_v4(),
+        file_name
+    );
+
+    file.persist_to(&tmp_file_path_str)
+        .await
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    let result = patch::check(Path::new(&tmp_file_path_str))
+        .map_err(|err| match err.kind() {
+            ErrorKind::InvalidData => ApiError::new(&err.to_string(), Status::BadRequest),
+            _ => ApiError::default(err.to_string().as_str()),
+        })
+        .and_then(|_| {
+            std::fs::rename(&tmp_file_path_str, destination)
+                .map_err(|err| ApiError::default(err.to_string().as_str()))
+        });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_file_path_str);
+    }
+
+    result
+}
+
+fn check_version_exist(name: &str, version: Option<u32>) -> Result<(), ApiError> {
+    match version {
+        Some(version) if !snapshots::snapshot_exist(name, version) => Err(ApiError::new(
+            "The template version doesn't exist.",
+            Status::NotFound,
+        )),
+        _ => Ok(()),
+    }
+}
+
 #[get("/")]
-pub async fn get_templates() -> Result<ApiSuccess, ApiError> {
+pub async fn get_templates(_role: ReadOnly) -> Result<ApiSuccess, ApiError> {
     let templates =
         manager::get_templates().map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
@@ -28,7 +125,9 @@ pub async fn get_templates() -> Result<ApiSuccess, ApiError> {
 }
 
 #[get("/<name>")]
-pub async fn get_template(name: String) -> Result<ApiSuccess, ApiError> {
+pub async fn get_template(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::template_exist(&name) {
         return Err(ApiError::new(
             "The template doesn't exist.",
@@ -48,9 +147,11 @@ pub async fn get_template(name: String) -> Result<ApiSuccess, ApiError> {
 }
 
 #[post("/create", data = "<data>")]
-pub async fn create(data: Json<Template>) -> Result<ApiSuccess, ApiError> {
+pub async fn create(_role: Editor, data: Json<Template>) -> Result<ApiSuccess, ApiError> {
     let template = data.into_inner();
 
+    validate_template_names(&template)?;
+
     if !parents::manager::parent_exist(&template.parent) {
         return Err(ApiError::new(
             "The specified parent doesn't exist.",
@@ -58,6 +159,11 @@ pub async fn create(data: Json<Template>) -> Result<ApiSuccess, ApiError> {
         ));
     }
 
+    template
+        .resources
+        .validate()
+        .map_err(|err| ApiError::new(err, Status::BadRequest))?;
+
     let template_name = &template.name;
 
     if manager::template_exist(template_name) {
@@ -67,16 +173,10 @@ pub async fn create(data: Json<Template>) -> Result<ApiSuccess, ApiError> {
         ));
     }
 
-    let maps_name = &template.maps;
+    // Held until the details are written, so the maps can't be deleted in the meantime.
+    let _maps_guard = maps::manager::lock_maps();
 
-    for map_name in maps_name {
-        if !maps::manager::map_exist(map_name) {
-            return Err(ApiError::new(
-                "A specified map doesn't exist.",
-                Status::BadRequest,
-            ));
-        }
-    }
+    check_maps_exist(&template)?;
 
     init_dirs(template_name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
@@ -88,11 +188,15 @@ pub async fn create(data: Json<Template>) -> Result<ApiSuccess, ApiError> {
     serde_json::to_writer_pretty(details_file, &template)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    Ok(ApiSuccess::default("The template has been created."))
+    let warning = snapshots::record_snapshot_or_warn(template_name, "Template created").err();
+
+    Ok(ApiSuccess::default("The template has been created.").with_warning(warning))
 }
 
 #[delete("/<name>/delete")]
-pub async fn delete(name: String) -> Result<ApiSuccess, ApiError> {
+pub async fn delete(_role: Admin, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::template_exist(&name) {
         return Err(ApiError::new(
             "The template doesn't exist.",
@@ -105,15 +209,28 @@ pub async fn delete(name: String) -> Result<ApiSuccess, ApiError> {
     std::fs::remove_dir_all(template_path_str)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
+    snapshots::delete_snapshots(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    builds::history::delete_builds(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    cache::invalidate_template(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
     Ok(ApiSuccess::default("The template has been deleted."))
 }
 
+// Editors can update a template, it is only rebuilt when the token is allowed to build.
 #[put("/<name>/update", data = "<data>")]
 pub async fn update(
+    _role: Editor,
+    admin: Option<Admin>,
     name: String,
     data: Json<Template>,
-    config: &State<Config>,
+    queue: &State<BuildQueue>,
 ) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::template_exist(&name) {
         return Err(ApiError::new(
             "The template doesn't exist.",
@@ -122,6 +239,18 @@ pub async fn update(
     }
 
     let template = data.into_inner();
+
+    validate_template_names(&template)?;
+
+    template
+        .resources
+        .validate()
+        .map_err(|err| ApiError::new(err, Status::BadRequest))?;
+
+    let maps_guard = maps::manager::lock_maps();
+
+    check_maps_exist(&template)?;
+
     let template_path_str = manager::get_template_path(&name);
 
     let new_name = &template.name;
@@ -130,6 +259,12 @@ pub async fn update(
     std::fs::rename(template_path_str, new_template_path_str)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
+    snapshots::move_snapshots(&name, new_name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    builds::history::move_builds(&name, new_name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
     let new_details_file_path_str = manager::get_details_file_path(new_name);
     let new_details_file = File::create(new_details_file_path_str)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
@@ -137,15 +272,35 @@ pub async fn update(
     serde_json::to_writer_pretty(new_details_file, &template)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    utils::build_template_dockerfile(&template, config)
-        .await
+    let warning = snapshots::record_snapshot_or_warn(new_name, "Template updated").err();
+
+    drop(maps_guard);
+
+    cache::invalidate_template(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    if admin.is_none() {
+        return Ok(ApiSuccess::default("The template has been updated.").with_warning(warning));
+    }
+
+    let build = queue
+        .enqueue(new_name, Mode::Remote, None)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    Ok(ApiSuccess::default("The template has been updated."))
+    Ok(ApiSuccess::data(json!({
+        "success": "The template has been updated.",
+        "build": build
+    }))
+    .with_warning(warning))
 }
 
 #[post("/<name>/plugins/push", data = "<data>")]
-pub async fn push_plugin(name: String, mut data: Form<Upload<'_>>) -> Result<ApiSuccess, ApiError> {
+pub async fn push_plugin(
+    _role: Editor,
+    name: String,
+    mut data: Form<Upload<'_>>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::template_exist(&name) {
         return Err(ApiError::new(
             "The template doesn't exist.",
@@ -154,21 +309,165 @@ pub async fn push_plugin(name: String, mut data: Form<Upload<'_>>) -> Result<Api
     }
 
     let file = &mut data.upload;
-    let raw_name = file.raw_name().unwrap();
-    let file_name = raw_name.dangerous_unsafe_unsanitized_raw();
+    let file_name = safe_path::get_upload_file_name(file)?;
 
     let plugin_path_str = manager::get_template_plugins_path(&name);
-    let plugin_file_path = format!("{}/{}", plugin_path_str, file_name);
+    let plugin_file_path = safe_path::join(&plugin_path_str, &file_name)?;
 
-    file.persist_to(plugin_file_path)
-        .await
+    plugins::manager::install(file, &plugin_file_path, None).await?;
+
+    let warning =
+        snapshots::record_snapshot_or_warn(&name, &format!("Plugin {} pushed", file_name)).err();
+
+    cache::invalidate_template(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    let template = manager::get_template_obj(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    let validation = plugins::validation::validate_template(&template)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    Ok(ApiSuccess::default("The plugin has been pushed."))
+    Ok(ApiSuccess::data(json!({
+        "success": "The plugin has been pushed.",
+        "validation": validation
+    }))
+    .with_warning(warning))
+}
+
+// Checks the plugins the template ends up with, its own and its parent ones.
+#[get("/<name>/validate")]
+pub async fn validate(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    let template = manager::get_template_obj(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    if !parents::manager::parent_exist(&template.parent) {
+        return Err(ApiError::new(
+            "The template's parent doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    let validation = plugins::validation::validate_template(&template)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::data(json!(validation)))
+}
+
+#[get("/<name>/plugins")]
+pub async fn get_plugins(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    let plugins = plugins::manager::get_plugins(&manager::get_template_plugins_path(&name))
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::data(json!(plugins)))
+}
+
+#[delete("/<name>/plugins/<plugin>/delete")]
+pub async fn delete_plugin(
+    _role: Admin,
+    name: String,
+    plugin: String,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    let plugin_file_path = safe_path::join(&manager::get_template_plugins_path(&name), &plugin)?;
+
+    if !plugin_file_path.is_file() {
+        return Err(ApiError::new("The plugin doesn't exist.", Status::NotFound));
+    }
+
+    plugins::manager::remove(&plugin_file_path)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    let warning =
+        snapshots::record_snapshot_or_warn(&name, &format!("Plugin {} deleted", plugin)).err();
+
+    cache::invalidate_template(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The plugin has been deleted.").with_warning(warning))
+}
+
+// The uploaded jar takes the place of the given one, which may have another name.
+#[post("/<name>/plugins/<plugin>/replace", data = "<data>")]
+pub async fn replace_plugin(
+    _role: Editor,
+    name: String,
+    plugin: String,
+    mut data: Form<Upload<'_>>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    let file = &mut data.upload;
+    let file_name = safe_path::get_upload_file_name(file)?;
+
+    let plugin_path_str = manager::get_template_plugins_path(&name);
+    let replaced_file_path = safe_path::join(&plugin_path_str, &plugin)?;
+    let plugin_file_path = safe_path::join(&plugin_path_str, &file_name)?;
+
+    if !replaced_file_path.is_file() {
+        return Err(ApiError::new("The plugin doesn't exist.", Status::NotFound));
+    }
+
+    plugins::manager::install(file, &plugin_file_path, Some(&replaced_file_path)).await?;
+
+    let warning = snapshots::record_snapshot_or_warn(
+        &name,
+        &format!("Plugin {} replaced by {}", plugin, file_name),
+    )
+    .err();
+
+    cache::invalidate_template(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    let template = manager::get_template_obj(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    let validation = plugins::validation::validate_template(&template)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::data(json!({
+        "success": "The plugin has been replaced.",
+        "validation": validation
+    }))
+    .with_warning(warning))
 }
 
 #[post("/<name>/main/push", data = "<data>")]
-pub async fn push_file(name: String, mut data: Form<Upload<'_>>) -> Result<ApiSuccess, ApiError> {
+pub async fn push_file(
+    _role: Editor,
+    name: String,
+    mut data: Form<Upload<'_>>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::template_exist(&name) {
         return Err(ApiError::new(
             "The template doesn't exist.",
@@ -177,21 +476,30 @@ pub async fn push_file(name: String, mut data: Form<Upload<'_>>) -> Result<ApiSu
     }
 
     let file = &mut data.upload;
-    let raw_name = file.raw_name().unwrap();
-    let file_name = raw_name.dangerous_unsafe_unsanitized_raw();
+    let file_name = safe_path::get_upload_file_name(file)?;
 
     let template_path_str = manager::get_template_path(&name);
-    let new_file_path = format!("{}/{}", template_path_str, file_name);
+    let new_file_path = safe_path::join(&template_path_str, &file_name)?;
 
-    file.persist_to(new_file_path)
-        .await
-        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    persist_upload(file, &new_file_path).await?;
+
+    let warning =
+        snapshots::record_snapshot_or_warn(&name, &format!("File {} pushed", file_name)).err();
+
+    cache::invalidate_template(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    Ok(ApiSuccess::default("The file has been pushed."))
+    Ok(ApiSuccess::default("The file has been pushed.").with_warning(warning))
 }
 
-#[get("/<name>/zip")]
-pub async fn to_zip(name: String) -> Result<File, ApiError> {
+// The destination is a path relative to the template directory, missing directories are created.
+#[post("/<name>/files/push", data = "<data>")]
+pub async fn push_nested_file(
+    _role: Editor,
+    name: String,
+    mut data: Form<NestedUpload<'_>>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::template_exist(&name) {
         return Err(ApiError::new(
             "The template doesn't exist.",
@@ -199,48 +507,143 @@ pub async fn to_zip(name: String) -> Result<File, ApiError> {
         ));
     }
 
-    let template_path = manager::get_template_path(&name);
-    let template = manager::get_template_obj(&name)
+    let new_file_path = files::prepare_upload_path(&manager::get_template_path(&name), &data.path)?;
+
+    persist_upload(&mut data.upload, &new_file_path).await?;
+
+    let warning =
+        snapshots::record_snapshot_or_warn(&name, &format!("File {} pushed", data.path)).err();
+
+    cache::invalidate_template(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The file has been pushed.").with_warning(warning))
+}
+
+#[get("/<name>/zip?<version>")]
+pub async fn to_zip(
+    _role: ReadOnly,
+    name: String,
+    version: Option<u32>,
+    if_none_match: IfNoneMatch,
+) -> Result<CachedArchive, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    check_version_exist(&name, version)?;
+
+    let template_path = snapshots::get_template_content_path(&name, version)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    let template = snapshots::get_template_obj(&name, version)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
-    let template_parent_name = template.parent;
+    let template_parent_name = &template.parent;
 
-    if !parents::manager::parent_exist(&template_parent_name) {
+    if !parents::manager::parent_exist(template_parent_name) {
         return Err(ApiError::new(
             "The template's parent doesn't exist.",
             Status::NotFound,
         ));
     }
 
-    let tmp_path_str = format!("{}/{}", global::TMP_DIR, name);
-    let template_parent_path = parents::manager::get_parent_path(&template_parent_name);
+    let resolved_maps =
+        maps::manager::resolve_all(&template.maps).map_err(|err| match err.kind() {
+            ErrorKind::NotFound => ApiError::new(&err.to_string(), Status::NotFound),
+            _ => ApiError::default(err.to_string().as_str()),
+        })?;
 
-    std::fs::create_dir_all(&tmp_path_str)
+    let key = cache::get_archive_key(&template, &template_path, &resolved_maps)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    let etag = format!("\"{}\"", key);
+    let maps = format_resolved_maps(&resolved_maps);
 
-    let parent_paths = glob::glob(&format!("{}/**/*", template_parent_path)).unwrap();
-    let template_paths = glob::glob(&format!("{}/**/*", template_path)).unwrap();
+    if if_none_match.matches(&etag) {
+        return Ok(CachedArchive::NotModified { etag, maps });
+    }
 
-    let zip_file_path = format!("{}/{}.zip", tmp_path_str, name);
+    let body = cache::get_archive(template, template_path, version, resolved_maps, &key).map_err(
+        |err| match err.kind() {
+            ErrorKind::InvalidData => ApiError::new(&err.to_string(), Status::UnprocessableEntity),
+            _ => ApiError::default(err.to_string().as_str()),
+        },
+    )?;
 
-    let file =
-        File::create(&zip_file_path).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+    Ok(CachedArchive::Archive { body, etag, maps })
+}
 
-    let mut zip = ZipWriter::new(file);
+#[post("/<name>/build?<mode>&<version>")]
+pub async fn build(
+    _role: Admin,
+    name: String,
+    mode: Option<Mode>,
+    version: Option<u32>,
+    queue: &State<BuildQueue>,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
 
-    utils::write_paths_in_zip(&mut zip, parent_paths, template_parent_name.as_str())
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    check_version_exist(&name, version)?;
+
+    let build = queue
+        .enqueue(&name, mode.unwrap_or_default(), version)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    utils::write_paths_in_zip(&mut zip, template_paths, &name)
+    Ok(ApiSuccess::new(json!(build), Status::Accepted))
+}
+
+#[get("/<name>/builds")]
+pub async fn get_builds(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    let history = builds::history::get_history(&name)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    zip.finish()
-        .map_err(|_err| ApiError::default("An error occurred on finish writing zip file."))?;
+    Ok(ApiSuccess::data(json!(history)))
+}
+
+#[get("/<name>/snapshots")]
+pub async fn get_snapshots(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    let snapshots = snapshots::get_snapshots(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    Ok(File::open(&zip_file_path).unwrap())
+    Ok(ApiSuccess::data(json!(snapshots)))
 }
 
-#[post("/<name>/build")]
-pub async fn build(name: String, config: &State<Config>) -> Result<ApiSuccess, ApiError> {
+#[get("/<name>/snapshots/<from>/diff/<to>")]
+pub async fn diff_snapshots(
+    _role: ReadOnly,
+    name: String,
+    from: u32,
+    to: u32,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
     if !manager::template_exist(&name) {
         return Err(ApiError::new(
             "The template doesn't exist.",
@@ -248,14 +651,157 @@ pub async fn build(name: String, config: &State<Config>) -> Result<ApiSuccess, A
         ));
     }
 
-    let current_template = manager::get_template_obj(&name)
+    check_version_exist(&name, Some(from))?;
+    check_version_exist(&name, Some(to))?;
+
+    let diff = snapshots::diff(&name, from, to)
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    utils::build_template_dockerfile(&current_template, config)
-        .await
+    Ok(ApiSuccess::data(json!(diff)))
+}
+
+#[post("/<name>/snapshots/<version>/rollback")]
+pub async fn rollback(_role: Editor, name: String, version: u32) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    check_version_exist(&name, Some(version))?;
+
+    // The maps of the snapshot may have been deleted since, held like on update so that none is
+    // deleted while it is brought back.
+    let maps_guard = maps::manager::lock_maps();
+
+    let snapshot_template = snapshots::get_template_obj(&name, Some(version))
         .map_err(|err| ApiError::default(err.to_string().as_str()))?;
 
-    Ok(ApiSuccess::default(
-        "The image has been built on the registry.",
-    ))
+    check_maps_exist(&snapshot_template)?;
+
+    snapshots::rollback(&name, version)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    let snapshot =
+        snapshots::record_snapshot_or_warn(&name, &format!("Rollback to version {}", version));
+
+    drop(maps_guard);
+
+    cache::invalidate_template(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::data(json!({
+        "success": "The template has been rolled back.",
+        "snapshot": snapshot.as_ref().ok()
+    }))
+    .with_warning(snapshot.err()))
+}
+
+#[get("/<name>/files")]
+pub async fn get_files(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    let files = files::list_files(&manager::get_template_path(&name))?;
+
+    Ok(ApiSuccess::data(json!(files)))
+}
+
+// Lists what the template archive holds and whether each entry comes from the parent or the
+// template.
+#[get("/<name>/files/merged")]
+pub async fn get_merged_files(_role: ReadOnly, name: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    let template = manager::get_template_obj(&name)
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    if !parents::manager::parent_exist(&template.parent) {
+        return Err(ApiError::new(
+            "The template's parent doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    let entries = merge::get_merged_entries(&template, &manager::get_template_path(&name))
+        .map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::data(json!(entries)))
+}
+
+#[get("/<name>/files/download?<path>")]
+pub async fn download_file(_role: ReadOnly, name: String, path: String) -> Result<File, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    files::open_file(&manager::get_template_path(&name), &path)
+}
+
+#[put("/<name>/files/rename?<path>&<to>")]
+pub async fn rename_file(
+    _role: Editor,
+    name: String,
+    path: String,
+    to: String,
+) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    files::rename_file(&manager::get_template_path(&name), &path, &to)?;
+
+    let warning =
+        snapshots::record_snapshot_or_warn(&name, &format!("File {} renamed to {}", path, to))
+            .err();
+
+    cache::invalidate_template(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The file has been renamed.").with_warning(warning))
+}
+
+#[delete("/<name>/files/delete?<path>")]
+pub async fn delete_file(_role: Admin, name: String, path: String) -> Result<ApiSuccess, ApiError> {
+    safe_path::validate_name(&name)?;
+
+    if !manager::template_exist(&name) {
+        return Err(ApiError::new(
+            "The template doesn't exist.",
+            Status::NotFound,
+        ));
+    }
+
+    files::delete_file(&manager::get_template_path(&name), &path)?;
+
+    let warning =
+        snapshots::record_snapshot_or_warn(&name, &format!("File {} deleted", path)).err();
+
+    cache::invalidate_template(&name).map_err(|err| ApiError::default(err.to_string().as_str()))?;
+
+    Ok(ApiSuccess::default("The file has been deleted.").with_warning(warning))
 }
@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::templates::template::Template;
+use crate::{files, global};
+
+use super::{manager, utils};
+
+static SNAPSHOTS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Snapshot {
+    pub version: u32,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct SnapshotDiff {
+    pub from: u32,
+    pub to: u32,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+pub fn get_snapshots_path(name: &str) -> String {
+    format!("{}/{}", global::SNAPSHOTS_DIR, name)
+}
+
+pub fn get_snapshot_path(name: &str, version: u32) -> String {
+    format!("{}/{}", get_snapshots_path(name), version)
+}
+
+pub fn get_snapshots_file_path(name: &str) -> String {
+    format!("{}/snapshots.epsilon", get_snapshots_path(name))
+}
+
+pub fn snapshot_exist(name: &str, version: u32) -> bool {
+    Path::new(&get_snapshot_path(name, version)).is_dir()
+}
+
+pub fn get_snapshots(name: &str) -> Result<Vec<Snapshot>, Error> {
+    let snapshots_file_path_str = get_snapshots_file_path(name);
+
+    if !Path::new(&snapshots_file_path_str).exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(snapshots_file_path_str)?;
+
+    Ok(serde_json::from_reader(&file)?)
+}
+
+fn write_snapshots(name: &str, snapshots: &Vec<Snapshot>) -> Result<(), Error> {
+    let file = File::create(get_snapshots_file_path(name))?;
+
+    Ok(serde_json::to_writer_pretty(file, snapshots)?)
+}
+
+// Returns the directory holding the template content, either the live one or a snapshot.
+pub fn get_template_content_path(name: &str, version: Option<u32>) -> Result<String, Error> {
+    match version {
+        None => Ok(manager::get_template_path(name)),
+        Some(version) if snapshot_exist(name, version) => Ok(get_snapshot_path(name, version)),
+        Some(version) => Err(Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("The version {} of the template doesn't exist.", version),
+        )),
+    }
+}
+
+pub fn get_template_obj(name: &str, version: Option<u32>) -> Result<Template, Error> {
+    let content_path_str = get_template_content_path(name, version)?;
+    let file = File::open(format!("{}/details.epsilon", content_path_str))?;
+    let mut template: Template = serde_json::from_reader(&file)?;
+
+    // A snapshot taken before a rename still holds the previous name.
+    template.name = name.to_string();
+
+    Ok(template)
+}
+
+pub fn get_snapshot_templates(name: &str) -> Result<Vec<Template>, Error> {
+    get_snapshots(name)?
+        .iter()
+        .map(|snapshot| get_template_obj(name, Some(snapshot.version)))
+        .collect()
+}
+
+fn record_snapshot(name: &str, reason: &str) -> Result<Snapshot, Error> {
+    let _guard = SNAPSHOTS_LOCK.lock().unwrap();
+
+    std::fs::create_dir_all(get_snapshots_path(name))?;
+
+    let mut snapshots = get_snapshots(name)?;
+    let last_version = snapshots.iter().map(|snapshot| snapshot.version).max();
+
+    let snapshot = Snapshot {
+        version: last_version.unwrap_or(0) + 1,
+        reason: reason.to_string(),
+        created_at: Utc::now(),
+    };
+
+    let snapshot_path_str = get_snapshot_path(name, snapshot.version);
+    let previous_snapshot_path_str = last_version.map(|version| get_snapshot_path(name, version));
+
+    let result = copy_dir_all(
+        Path::new(&manager::get_template_path(name)),
+        Path::new(&snapshot_path_str),
+        previous_snapshot_path_str.as_deref().map(Path::new),
+    );
+
+    if let Err(err) = result {
+        let _ = std::fs::remove_dir_all(&snapshot_path_str);
+
+        return Err(err);
+    }
+
+    snapshots.push(snapshot.clone());
+    write_snapshots(name, &snapshots)?;
+
+    Ok(snapshot)
+}
+
+// The change is written by the time its snapshot is recorded, a failure doesn't undo it and is
+// returned as a warning for the response.
+pub fn record_snapshot_or_warn(name: &str, reason: &str) -> Result<Snapshot, String> {
+    record_snapshot(name, reason).map_err(|err| {
+        error!("Failed to record a snapshot of {}: {}", name, err);
+
+        format!("The change has been saved without a snapshot: {}", err)
+    })
+}
+
+// The snapshot is copied aside and swapped in with the template, so a failure leaves the template as
+// it was.
+pub fn rollback(name: &str, version: u32) -> Result<(), Error> {
+    let _guard = SNAPSHOTS_LOCK.lock().unwrap();
+
+    let template_path_str = manager::get_template_path(name);
+    let snapshot_path_str = get_template_content_path(name, Some(version))?;
+    let id = uuid::Uuid::new_v4();
+    let new_template_path_str = format!("{}/{}.{}.tmp", global::DATA_TMP_FILES_DIR, name, id);
+    let old_template_path_str = format!("{}/{}.{}.old", global::DATA_TMP_FILES_DIR, name, id);
+
+    let result = copy_dir_all(
+        Path::new(&snapshot_path_str),
+        Path::new(&new_template_path_str),
+        None,
+    )
+    .and_then(|_| {
+        // A snapshot taken before a rename still holds the previous name.
+        let template = get_template_obj(name, Some(version))?;
+        let details_file = File::create(format!("{}/details.epsilon", new_template_path_str))?;
+
+        Ok(serde_json::to_writer_pretty(details_file, &template)?)
+    })
+    .and_then(|_| std::fs::rename(&template_path_str, &old_template_path_str));
+
+    if let Err(err) = result {
+        let _ = std::fs::remove_dir_all(&new_template_path_str);
+
+        return Err(err);
+    }
+
+    if let Err(err) = std::fs::rename(&new_template_path_str, &template_path_str) {
+        let _ = std::fs::rename(&old_template_path_str, &template_path_str);
+        let _ = std::fs::remove_dir_all(&new_template_path_str);
+
+        return Err(err);
+    }
+
+    if let Err(err) = std::fs::remove_dir_all(&old_template_path_str) {
+        error!("Failed to remove the previous content of {}: {}", name, err);
+    }
+
+    Ok(())
+}
+
+pub fn diff(name: &str, from: u32, to: u32) -> Result<SnapshotDiff, Error> {
+    let from_hashes = hash_dir(&get_template_content_path(name, Some(from))?)?;
+    let to_hashes = hash_dir(&get_template_content_path(name, Some(to))?)?;
+
+    let mut diff = SnapshotDiff {
+        from,
+        to,
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+    };
+
+    for (path, hash) in &to_hashes {
+        match from_hashes.get(path) {
+            None => diff.added.push(path.clone()),
+            Some(from_hash) if from_hash != hash => diff.modified.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+
+    for path in from_hashes.keys() {
+        if !to_hashes.contains_key(path) {
+            diff.removed.push(path.clone());
+        }
+    }
+
+    Ok(diff)
+}
+
+pub fn move_snapshots(name: &str, new_name: &str) -> Result<(), Error> {
+    let _guard = SNAPSHOTS_LOCK.lock().unwrap();
+    let snapshots_path_str = get_snapshots_path(name);
+
+    if name == new_name || !Path::new(&snapshots_path_str).exists() {
+        return Ok(());
+    }
+
+    std::fs::rename(snapshots_path_str, get_snapshots_path(new_name))
+}
+
+pub fn delete_snapshots(name: &str) -> Result<(), Error> {
+    let _guard = SNAPSHOTS_LOCK.lock().unwrap();
+    let snapshots_path_str = get_snapshots_path(name);
+
+    if !Path::new(&snapshots_path_str).exists() {
+        return Ok(());
+    }
+
+    std::fs::remove_dir_all(snapshots_path_str)
+}
+
+fn hash_dir(dir_path: &str) -> Result<BTreeMap<String, String>, Error> {
+    let mut hashes = BTreeMap::new();
+    let paths = glob::glob(&format!("{}/**/*", dir_path)).map_err(Error::other)?;
+
+    for glob_result in paths {
+        let path = glob_result.map_err(Error::other)?;
+
+        if !path.is_file() {
+            continue;
+        }
+
+        let relative_path = utils::strip_base_path(&path, dir_path)?;
+
+        hashes.insert(
+            relative_path.to_string_lossy().to_string(),
+            files::hash_file(&path)?,
+        );
+    }
+
+    Ok(hashes)
+}
+
+// Files unchanged since the previous snapshot are hard linked to it rather than copied, snapshots
+// are never modified and most changes leave the plugins and worlds as they are.
+fn copy_dir_all(source: &Path, destination: &Path, previous: Option<&Path>) -> Result<(), Error> {
+    std::fs::create_dir_all(destination)?;
+
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+
+        copy_entry(
+            &entry,
+            &destination.join(entry.file_name()),
+            previous.map(|previous| previous.join(entry.file_name())),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn copy_entry(
+    entry: &std::fs::DirEntry,
+    destination: &Path,
+    previous: Option<PathBuf>,
+) -> Result<(), Error> {
+    if entry.file_type()?.is_dir() {
+        return copy_dir_all(&entry.path(), destination, previous.as_deref());
+    }
+
+    if let Some(previous) = previous {
+        if previous.is_file()
+            && files_equal(&entry.path(), &previous)?
+            && std::fs::hard_link(&previous, destination).is_ok()
+        {
+            return Ok(());
+        }
+    }
+
+    std::fs::copy(entry.path(), destination)?;
+
+    Ok(())
+}
+
+fn files_equal(path: &Path, other_path: &Path) -> Result<bool, Error> {
+    if std::fs::metadata(path)?.len() != std::fs::metadata(other_path)?.len() {
+        return Ok(false);
+    }
+
+    Ok(files::hash_file(path)? == files::hash_file(other_path)?)
+}
@@ -1,3 +1,4 @@
+use crate::maps::reference::MapReference;
 use crate::parents::parent::Type;
 use crate::templates::resources::Resources;
 use rocket::serde::json::Value;
@@ -14,7 +15,26 @@ pub struct Template {
     pub t: Option<Type>,
     pub slots: u16,
     pub default_map: String,
+    // References to maps, "name" for the latest version or "name@version".
     pub maps: Vec<String>,
     pub resources: Resources,
     pub labels: HashMap<String, Value>,
 }
+
+impl Template {
+    pub fn uses_map(&self, map_name: &str) -> bool {
+        self.maps.iter().any(|reference| {
+            reference
+                .parse::<MapReference>()
+                .is_ok_and(|reference| reference.name == map_name)
+        })
+    }
+
+    pub fn uses_map_version(&self, map_name: &str, version: u32) -> bool {
+        self.maps.iter().any(|reference| {
+            reference.parse::<MapReference>().is_ok_and(|reference| {
+                reference.name == map_name && reference.version == Some(version)
+            })
+        })
+    }
+}
@@ -1,138 +1,594 @@
 use bollard::auth::DockerCredentials;
-use bollard::image::{BuildImageOptions, PushImageOptions, RemoveImageOptions};
+use bollard::image::{BuildImageOptions, PushImageOptions, RemoveImageOptions, TagImageOptions};
 use bollard::Docker;
 use futures_util::StreamExt;
-use glob::Paths;
-use std::collections::HashMap;
+use hyper::Body;
+use rocket::tokio;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
-use std::io::{Error, ErrorKind, Read, Write};
-use std::path::PathBuf;
-use tar::Builder;
-use zip::write::FileOptions;
-use zip::ZipWriter;
+use std::io::{BufWriter, Error, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tar::{Builder, EntryType, Header};
+use tokio_util::io::ReaderStream;
+use zip::write::{SimpleFileOptions, StreamWriter};
+use zip::{ZipArchive, ZipWriter};
 
+use super::merge::{self, MergedEntry};
 use super::template::Template;
+use super::variables::{self, Variables};
+use super::{manager, snapshots};
+use crate::builds::build::{Build, Mode};
+use crate::builds::log::BuildLog;
 use crate::config::Config;
-use crate::global;
+use crate::maps::reference::{format_resolved_maps, ResolvedMap};
+use crate::{global, maps, parents};
+
+pub struct BuiltImage {
+    pub image: String,
+    pub digest: Option<String>,
+    pub maps: Option<Vec<ResolvedMap>>,
+}
 
 pub async fn build_template_dockerfile(
     current_template: &Template,
+    build: &Build,
     config: &Config,
-) -> Result<(), Error> {
-    let docker = Docker::connect_with_socket_defaults().unwrap();
+    log: &BuildLog,
+) -> Result<BuiltImage, Error> {
+    let docker = Docker::connect_with_socket_defaults().map_err(Error::other)?;
     let template_name = &current_template.name;
+    let resources = &current_template.resources;
 
-    let image_name = format!("{}/{}:latest", config.registry_host, template_name);
-    let mut build_args = HashMap::new();
+    resources.validate().map_err(Error::other)?;
+
+    let repository = format!("{}/{}", config.registry_host, template_name);
+    let min_heap_size = resources.minimum.get_heap_size();
+    let max_heap_size = resources.maximum.get_heap_size();
+    let mut build_args = BTreeMap::new();
 
     build_args.insert("TEMPLATE_NAME", template_name.as_str());
     build_args.insert("DEFAULT_MAP_NAME", current_template.default_map.as_str());
     build_args.insert("API_HOST", config.api_host.as_str());
+    build_args.insert("MIN_HEAP_SIZE", min_heap_size.as_str());
+    build_args.insert("MAX_HEAP_SIZE", max_heap_size.as_str());
 
-    let build_options = BuildImageOptions {
-        dockerfile: "Dockerfile",
-        t: &image_name,
-        buildargs: build_args,
-        rm: true,
-        forcerm: true,
-        pull: true,
-        ..Default::default()
+    let min_cpu = resources.minimum.cpu.to_string();
+    let max_cpu = resources.maximum.cpu.to_string();
+    let min_memory = resources.minimum.get_memory_quantity();
+    let max_memory = resources.maximum.get_memory_quantity();
+    let mut labels = BTreeMap::new();
+
+    labels.insert("org.opencontainers.image.title", template_name.as_str());
+    labels.insert("epsilon.template.parent", current_template.parent.as_str());
+    labels.insert("epsilon.resources.minimum.cpu", min_cpu.as_str());
+    labels.insert("epsilon.resources.maximum.cpu", max_cpu.as_str());
+    labels.insert("epsilon.resources.minimum.memory", min_memory.as_str());
+    labels.insert("epsilon.resources.maximum.memory", max_memory.as_str());
+
+    // Only baked images hold maps, remote ones download them with the archive when they start.
+    let resolved_maps = match build.mode {
+        Mode::Baked => Some(maps::manager::resolve_all(&current_template.maps)?),
+        Mode::Remote => None,
+    };
+    let maps_label = resolved_maps.as_deref().map(format_resolved_maps);
+
+    if let Some(maps_label) = &maps_label {
+        log.push(&format!("Using the maps {}", maps_label));
+        labels.insert("epsilon.maps", maps_label.as_str());
+    }
+
+    let dockerfile = get_template_dockerfile(current_template, build.mode, log)?;
+
+    // The build context can hold the whole template content with its maps, it is written to disk
+    // and streamed to the daemon instead of being held in memory.
+    let context_file_path_str = format!("{}/{}.tar", global::DATA_TMP_FILES_DIR, build.id);
+
+    let result = async {
+        write_build_context(
+            &context_file_path_str,
+            &dockerfile,
+            current_template,
+            build,
+            resolved_maps.as_deref(),
+            log,
+        )?;
+
+        let hash = hash_build_context(Path::new(&context_file_path_str), &[&build_args, &labels])?;
+        let number = build.number.unwrap_or_default();
+        let version = format!("{}-{}", number, &hash[..12]);
+        let image_name = format!("{}:{}", repository, version);
+        let latest_image_name = format!("{}:latest", repository);
+        let mut labels = labels;
+
+        labels.insert("org.opencontainers.image.version", version.as_str());
+
+        let build_options = BuildImageOptions {
+            dockerfile: "Dockerfile",
+            t: &image_name,
+            buildargs: build_args.into_iter().collect(),
+            labels: labels.into_iter().collect(),
+            rm: true,
+            forcerm: true,
+            pull: true,
+            ..Default::default()
+        };
+
+        log.push(&format!("Building {}", image_name));
+
+        let context_file = tokio::fs::File::open(&context_file_path_str).await?;
+        let context_body = Body::wrap_stream(ReaderStream::new(context_file));
+        let mut build_stream = docker.build_image(build_options, None, Some(context_body));
+
+        while let Some(build_info) = build_stream.next().await {
+            let build_info = build_info.map_err(Error::other)?;
+
+            if let Some(stream) = &build_info.stream {
+                log.push(stream);
+            }
+
+            if let Some(status) = &build_info.status {
+                log.push(&format_progress(
+                    build_info.id.as_deref(),
+                    status,
+                    build_info.progress.as_deref(),
+                ));
+            }
+
+            if let Some(error) = build_info.error {
+                return Err(Error::other(error));
+            }
+        }
+
+        let digest = push_image(&docker, &image_name, config, log).await?;
+
+        let tag_options = TagImageOptions {
+            repo: repository.as_str(),
+            tag: "latest",
+        };
+
+        docker
+            .tag_image(&image_name, Some(tag_options))
+            .await
+            .map_err(Error::other)?;
+
+        push_image(&docker, &latest_image_name, config, log).await?;
+
+        // The image is in the registry at this point, failing to clean it up locally doesn't
+        // fail the build.
+        for local_image_name in [&latest_image_name, &image_name] {
+            if let Err(err) = remove_image(&docker, local_image_name).await {
+                log.push(&format!(
+                    "Failed to remove the local image {}: {}",
+                    local_image_name, err
+                ));
+            }
+        }
+
+        Ok(BuiltImage {
+            image: image_name,
+            digest,
+            maps: resolved_maps.clone(),
+        })
+    }
+    .await;
+
+    let _ = std::fs::remove_file(&context_file_path_str);
+
+    result
+}
+
+fn write_build_context(
+    context_file_path: &str,
+    dockerfile: &[u8],
+    current_template: &Template,
+    build: &Build,
+    resolved_maps: Option<&[ResolvedMap]>,
+    log: &BuildLog,
+) -> Result<(), Error> {
+    let mut builder = Builder::new(BufWriter::new(File::create(context_file_path)?));
+    let mut dockerfile_header = Header::new_gnu();
+
+    dockerfile_header.set_mode(0o644);
+    dockerfile_header.set_size(dockerfile.len() as u64);
+    builder.append_data(&mut dockerfile_header, "Dockerfile", dockerfile)?;
+
+    if let Some(resolved_maps) = resolved_maps {
+        log.push("Adding the template content to the build context");
+
+        append_template_content_in_tar(
+            &mut builder,
+            current_template,
+            build,
+            resolved_maps,
+            "content",
+        )?;
+    }
+
+    builder.into_inner()?.flush()
+}
+
+// A Dockerfile pushed on the parent wins over its image spec, which wins over the global ones.
+fn get_template_dockerfile(
+    current_template: &Template,
+    mode: Mode,
+    log: &BuildLog,
+) -> Result<Vec<u8>, Error> {
+    let parent = manager::get_template_parent_obj(current_template)?;
+    let parent_dockerfile_path_str =
+        parents::manager::get_parent_dockerfile_path(&parent.name, mode);
+
+    if Path::new(&parent_dockerfile_path_str).exists() {
+        log.push(&format!(
+            "Using the Dockerfile of the parent {}",
+            parent.name
+        ));
+
+        return std::fs::read(parent_dockerfile_path_str);
+    }
+
+    // A Dockerfile written for the other mode either doesn't copy the content or downloads it, so
+    // the build doesn't fall back to the image spec or the global one behind its back.
+    let other_mode = match mode {
+        Mode::Remote => Mode::Baked,
+        Mode::Baked => Mode::Remote,
+    };
+
+    if Path::new(&parents::manager::get_parent_dockerfile_path(
+        &parent.name,
+        other_mode,
+    ))
+    .exists()
+    {
+        return Err(Error::other(format!(
+            "The parent {} only has a Dockerfile for the other build mode.",
+            parent.name
+        )));
+    }
+
+    if let Some(image) = &parent.image {
+        log.push(&format!(
+            "Using the image spec of the parent {} ({})",
+            parent.name,
+            image.get_base_image()
+        ));
+
+        return Ok(image.to_dockerfile(&parent.t, mode).into_bytes());
+    }
+
+    let dockerfile_path = match mode {
+        Mode::Remote => "./data/Dockerfile",
+        Mode::Baked => "./data/Dockerfile.baked",
     };
 
-    let dockerfile_path = "./data/Dockerfile";
-    let mut dockerfile = File::open(dockerfile_path).unwrap();
+    std::fs::read(dockerfile_path)
+}
 
-    let tmp_path_str = format!("{}/{}", global::TMP_DIR, template_name);
+fn append_template_content_in_tar<W: Write>(
+    builder: &mut Builder<W>,
+    current_template: &Template,
+    build: &Build,
+    resolved_maps: &[ResolvedMap],
+    destination: &str,
+) -> Result<(), Error> {
+    let parent_path = parents::manager::get_parent_path(&current_template.parent);
+    let template_path = snapshots::get_template_content_path(&build.template, build.version)?;
 
-    std::fs::create_dir_all(&tmp_path_str)?;
+    if !Path::new(&parent_path).is_dir() {
+        return Err(Error::other("The template's parent doesn't exist."));
+    }
+
+    let entries = merge::get_merged_entries(current_template, &template_path)?;
+    let variables = Variables::new(current_template);
+
+    variables::check_entries(&entries, &variables)?;
+
+    for entry in entries {
+        let entry_path = Path::new(destination).join(&entry.path);
+
+        if entry.directory {
+            builder.append_dir(entry_path, &entry.source)?;
+            continue;
+        }
+
+        match variables::render_entry(&entry, &variables)? {
+            Some(content) => {
+                let mut header = Header::new_gnu();
+
+                header.set_mode(0o644);
+                header.set_size(content.len() as u64);
+                builder.append_data(&mut header, entry_path, content.as_slice())?;
+            }
+            None => builder.append_path_with_name(&entry.source, entry_path)?,
+        }
+    }
 
-    let archive_file_path_str = format!("{}/{}.tar", tmp_path_str, template_name);
-    let archive_file = File::create(&archive_file_path_str)?;
+    for resolved_map in resolved_maps {
+        let map_destination = format!("{}/{}", destination, resolved_map.name);
 
-    let mut builder = Builder::new(archive_file);
+        append_map_in_tar(builder, resolved_map, &map_destination)?;
+    }
 
-    builder.append_file("Dockerfile", &mut dockerfile)?;
+    Ok(())
+}
 
-    builder.finish()?;
+fn append_map_in_tar<W: Write>(
+    builder: &mut Builder<W>,
+    resolved_map: &ResolvedMap,
+    destination: &str,
+) -> Result<(), Error> {
+    let map_file = File::open(maps::manager::get_version_path(
+        &resolved_map.name,
+        resolved_map.version,
+    ))?;
+    let mut map_archive = ZipArchive::new(map_file)?;
 
-    let mut contents = Vec::new();
-    File::open(&archive_file_path_str)?
-        .read_to_end(&mut contents)
-        .unwrap();
+    for i in 0..map_archive.len() {
+        let mut entry = map_archive.by_index(i)?;
 
-    let mut build_stream = docker.build_image(build_options, None, Some(contents.into()));
+        let entry_path = match entry.enclosed_name() {
+            Some(entry_path) => Path::new(destination).join(entry_path),
+            None => continue,
+        };
 
-    while let Some(build_info) = build_stream.next().await {
-        let build_info = build_info.map_err(|err| Error::new(ErrorKind::Other, err))?;
+        let mut header = Header::new_gnu();
 
-        if let Some(error) = build_info.error {
-            return Err(Error::new(ErrorKind::Other, error));
+        if entry.is_dir() {
+            header.set_entry_type(EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            builder.append_data(&mut header, entry_path, std::io::empty())?;
+        } else {
+            header.set_mode(0o644);
+            header.set_size(entry.size());
+            builder.append_data(&mut header, entry_path, &mut entry)?;
         }
     }
 
+    Ok(())
+}
+
+fn hash_build_context(
+    context_file_path: &Path,
+    options: &[&BTreeMap<&str, &str>],
+) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+
+    std::io::copy(&mut File::open(context_file_path)?, &mut hasher)?;
+
+    for (key, value) in options.iter().flat_map(|option| option.iter()) {
+        hasher.update(format!("{}={}\n", key, value));
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+async fn push_image(
+    docker: &Docker,
+    image_name: &str,
+    config: &Config,
+    log: &BuildLog,
+) -> Result<Option<String>, Error> {
     let credentials = DockerCredentials {
         username: Some(String::from(&config.registry_username)),
         password: Some(String::from(&config.registry_password)),
         ..Default::default()
     };
 
+    log.push(&format!("Pushing {}", image_name));
+
     let mut push_stream = docker.push_image(
-        &image_name,
+        image_name,
         None::<PushImageOptions<String>>,
         Some(credentials),
     );
 
+    let mut digest = None;
+
     while let Some(push_info) = push_stream.next().await {
-        let push_info = push_info.map_err(|err| Error::new(ErrorKind::Other, err))?;
+        let push_info = push_info.map_err(Error::other)?;
+
+        if let Some(status) = &push_info.status {
+            log.push(&format_progress(
+                None,
+                status,
+                push_info.progress.as_deref(),
+            ));
+
+            // The registry reports the manifest digest as "<tag>: digest: sha256:... size: ..."
+            if let Some((_, rest)) = status.split_once("digest: ") {
+                digest = rest.split_whitespace().next().map(String::from);
+            }
+        }
 
         if let Some(error) = push_info.error {
-            return Err(Error::new(ErrorKind::Other, error));
+            return Err(Error::other(error));
         }
     }
 
+    Ok(digest)
+}
+
+async fn remove_image(docker: &Docker, image_name: &str) -> Result<(), Error> {
     let remove_image_options = RemoveImageOptions {
         force: true,
         ..Default::default()
     };
 
     let remove_image_stream = docker
-        .remove_image(&image_name, Some(remove_image_options), None)
+        .remove_image(image_name, Some(remove_image_options), None)
         .await
-        .map_err(|err| Error::new(ErrorKind::Other, err))?;
+        .map_err(Error::other)?;
 
     if remove_image_stream.is_empty() {
-        return Err(Error::new(ErrorKind::Other, "Failed to remove image"));
+        return Err(Error::other("Failed to remove image"));
+    }
+
+    Ok(())
+}
+
+fn format_progress(id: Option<&str>, status: &str, progress: Option<&str>) -> String {
+    let mut line = String::new();
+
+    if let Some(id) = id {
+        line.push_str(id);
+        line.push_str(": ");
+    }
+
+    line.push_str(status);
+
+    if let Some(progress) = progress {
+        line.push(' ');
+        line.push_str(progress);
+    }
+
+    line
+}
+
+// The zip is written as a stream, each entry being followed by a data descriptor, so the writer
+// doesn't need to seek back and files are copied with a fixed-size buffer.
+pub fn write_template_zip<W: Write>(
+    writer: W,
+    current_template: &Template,
+    template_path: &str,
+    resolved_maps: &[ResolvedMap],
+) -> Result<W, Error> {
+    let entries = merge::get_merged_entries(current_template, template_path)?;
+    let variables = Variables::new(current_template);
+
+    let aborted = Arc::new(AtomicBool::new(false));
+    let mut zip = ZipWriter::new_stream(AbortableWriter {
+        inner: writer,
+        aborted: aborted.clone(),
+    });
+    let mut written_names = HashSet::new();
+
+    let result =
+        write_entries_in_zip(&mut zip, &mut written_names, &entries, &variables).and_then(|_| {
+            for resolved_map in resolved_maps {
+                write_map_in_zip(
+                    &mut zip,
+                    &mut written_names,
+                    resolved_map,
+                    &resolved_map.name,
+                )?;
+            }
+
+            Ok(())
+        });
+
+    // The writer finishes the archive when dropped, a truncated one would then look complete.
+    if let Err(err) = result {
+        aborted.store(true, Ordering::Relaxed);
+
+        return Err(err);
+    }
+
+    Ok(zip.finish()?.into_inner().inner)
+}
+
+// Discards everything written once aborted, so an archive left without its central directory
+// can't be read.
+struct AbortableWriter<W: Write> {
+    inner: W,
+    aborted: Arc<AtomicBool>,
+}
+
+impl<W: Write> Write for AbortableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.aborted.load(Ordering::Relaxed) {
+            true => Ok(buf.len()),
+            false => self.inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.aborted.load(Ordering::Relaxed) {
+            true => Ok(()),
+            false => self.inner.flush(),
+        }
+    }
+}
+
+// An archive can't hold the same name twice, names already written, by a map folder colliding
+// with a template directory for instance, are skipped.
+pub fn write_entries_in_zip<W: Write>(
+    zip: &mut ZipWriter<StreamWriter<W>>,
+    written_names: &mut HashSet<String>,
+    entries: &[MergedEntry],
+    variables: &Variables,
+) -> Result<(), Error> {
+    for entry in entries {
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+
+        if entry.directory {
+            if written_names.insert(format!("{}/", entry.path)) {
+                zip.add_directory(entry.path.as_str(), options)?
+            }
+        } else if written_names.insert(entry.path.clone()) {
+            zip.start_file(entry.path.as_str(), options)?;
+
+            match variables::render_entry(entry, variables)? {
+                Some(content) => zip.write_all(&content)?,
+                None => {
+                    let mut file = File::open(&entry.source)?;
+
+                    std::io::copy(&mut file, zip)?;
+                }
+            }
+        }
     }
 
     Ok(())
 }
 
-pub fn write_paths_in_zip(
-    zip: &mut ZipWriter<File>,
-    paths: Paths,
-    prefix_path: &str,
+// The map entries are copied without being decompressed, under a world folder named after the map.
+pub fn write_map_in_zip<W: Write>(
+    zip: &mut ZipWriter<StreamWriter<W>>,
+    written_names: &mut HashSet<String>,
+    resolved_map: &ResolvedMap,
+    destination: &str,
 ) -> Result<(), Error> {
-    for glob_result in paths {
-        let path = glob_result.unwrap();
-        let path_without_prefix: PathBuf = path
-            .iter()
-            .skip_while(|s| *s != prefix_path)
-            .skip(1)
-            .collect();
-        let options = FileOptions::default().compression_method(zip::CompressionMethod::Stored);
-
-        if path.is_dir() {
-            zip.add_directory(path_without_prefix.to_str().unwrap(), options)?
-        } else if path.is_file() {
-            let mut file = File::open(&path)?;
-            let mut buffer = Vec::new();
-
-            file.read_to_end(&mut buffer)?;
-
-            zip.start_file(path_without_prefix.to_str().unwrap(), options)?;
-            zip.write_all(&buffer)?
+    let map_file = File::open(maps::manager::get_version_path(
+        &resolved_map.name,
+        resolved_map.version,
+    ))?;
+    let mut map_archive = ZipArchive::new(map_file)?;
+
+    for i in 0..map_archive.len() {
+        let entry = map_archive.by_index_raw(i)?;
+
+        let entry_path = match entry.enclosed_name() {
+            Some(entry_path) => Path::new(destination).join(entry_path),
+            None => continue,
+        };
+
+        let mut entry_name = entry_path
+            .to_str()
+            .unwrap()
+            .trim_end_matches('/')
+            .to_string();
+
+        if entry.is_dir() {
+            entry_name.push('/');
+        }
+
+        if written_names.insert(entry_name.clone()) {
+            zip.raw_copy_file_rename(entry, entry_name)?;
         }
     }
 
     Ok(())
 }
+
+// Glob drops the leading "./" of the patterns it is given, so it is ignored on both sides.
+pub fn strip_base_path<'a>(path: &'a Path, base_path: &str) -> Result<&'a Path, Error> {
+    let base_path = Path::new(base_path);
+    let base_path = base_path.strip_prefix(".").unwrap_or(base_path);
+    let path = path.strip_prefix(".").unwrap_or(path);
+
+    path.strip_prefix(base_path).map_err(Error::other)
+}
@@ -0,0 +1,242 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use rocket::serde::json::Value;
+
+use super::merge::{Layer, MergedEntry};
+use super::patch;
+use super::template::Template;
+
+// Only these files are rendered, binaries and files with placeholders of their own, like the
+// Log4j configurations, are copied as they are.
+const TEXT_EXTENSIONS: [&str; 8] = [
+    "properties",
+    "yml",
+    "yaml",
+    "json",
+    "toml",
+    "txt",
+    "conf",
+    "cfg",
+];
+
+// A placeholder is a template variable when its name starts with one of these, the others belong
+// to the plugins, like `${env:HOME}`.
+const VARIABLE_ROOTS: [&str; 5] = ["slots", "default_map", "template", "resources", "labels"];
+
+pub struct Variables(HashMap<String, String>);
+
+impl Variables {
+    pub fn new(template: &Template) -> Variables {
+        let mut variables = HashMap::new();
+        let resources = &template.resources;
+
+        variables.insert("slots".to_string(), template.slots.to_string());
+        variables.insert("default_map".to_string(), template.default_map.clone());
+        variables.insert("template.name".to_string(), template.name.clone());
+        variables.insert("template.parent".to_string(), template.parent.clone());
+        variables.insert("template.maps".to_string(), template.maps.join(","));
+        variables.insert(
+            "resources.minimum.cpu".to_string(),
+            resources.minimum.cpu.to_string(),
+        );
+        variables.insert(
+            "resources.minimum.ram".to_string(),
+            resources.minimum.ram.to_string(),
+        );
+        variables.insert(
+            "resources.maximum.cpu".to_string(),
+            resources.maximum.cpu.to_string(),
+        );
+        variables.insert(
+            "resources.maximum.ram".to_string(),
+            resources.maximum.ram.to_string(),
+        );
+
+        for (key, value) in &template.labels {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+
+            variables.insert(format!("labels.{}", key), value);
+        }
+
+        Variables(variables)
+    }
+
+    // Replaces every `${name}`, `$${` is kept as a literal `${`. Placeholders of the plugins are
+    // left untouched. Returns the unresolved names.
+    pub fn render(&self, content: &str) -> Result<String, BTreeSet<String>> {
+        let mut rendered = String::with_capacity(content.len());
+        let mut unresolved = BTreeSet::new();
+        let mut rest = content;
+
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                rendered.push_str(&rest[..start - 1]);
+                rendered.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+
+            let name = &rest[start + 2..end];
+
+            match self.0.get(name) {
+                Some(value) => {
+                    rendered.push_str(&rest[..start]);
+                    rendered.push_str(value);
+                }
+                None => {
+                    if is_variable_name(name) {
+                        unresolved.insert(name.to_string());
+                    }
+
+                    rendered.push_str(&rest[..=end]);
+                }
+            }
+
+            rest = &rest[end + 1..];
+        }
+
+        rendered.push_str(rest);
+
+        match unresolved.is_empty() {
+            true => Ok(rendered),
+            false => Err(unresolved),
+        }
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let root = name.split('.').next().unwrap_or(name);
+
+    VARIABLE_ROOTS.contains(&root)
+}
+
+pub fn is_text_file(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| TEXT_EXTENSIONS.contains(&ext))
+}
+
+// Returns the content of the entry when it differs from its source file, patched or rendered.
+pub fn render_entry(entry: &MergedEntry, variables: &Variables) -> Result<Option<Vec<u8>>, Error> {
+    let content = match entry.layer {
+        Layer::Patched => patch::apply(entry.base.as_deref(), &entry.source)?,
+        _ if is_text_file(&entry.path) => std::fs::read(&entry.source)?,
+        _ => return Ok(None),
+    };
+
+    // Text files in another encoding are left alone.
+    let text = match String::from_utf8(content) {
+        Ok(text) => text,
+        Err(err) => return Ok(Some(err.into_bytes())),
+    };
+
+    match variables.render(&text) {
+        Ok(rendered) => Ok(Some(rendered.into_bytes())),
+        Err(unresolved) => Err(unresolved_error(&entry.path, &unresolved)),
+    }
+}
+
+// Renders every entry without keeping the result, to report all unresolved variables at once.
+pub fn check_entries(entries: &[MergedEntry], variables: &Variables) -> Result<(), Error> {
+    let mut errors = Vec::new();
+
+    for entry in entries.iter().filter(|entry| !entry.directory) {
+        if let Err(err) = render_entry(entry, variables) {
+            match err.kind() {
+                ErrorKind::InvalidData => errors.push(err.to_string()),
+                _ => return Err(err),
+            }
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(Error::new(ErrorKind::InvalidData, errors.join(" "))),
+    }
+}
+
+fn unresolved_error(path: &str, unresolved: &BTreeSet<String>) -> Error {
+    let names: Vec<String> = unresolved
+        .iter()
+        .map(|name| format!("${{{}}}", name))
+        .collect();
+
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Unresolved variables in {}: {}.", path, names.join(", ")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> Variables {
+        Variables(HashMap::from([
+            ("slots".to_string(), "20".to_string()),
+            ("labels.mode".to_string(), "solo".to_string()),
+        ]))
+    }
+
+    #[test]
+    fn render_replaces_prefixed_variables() {
+        assert_eq!(
+            variables().render("max-players=${slots}\nmode: ${labels.mode}!"),
+            Ok("max-players=20\nmode: solo!".to_string())
+        );
+    }
+
+    #[test]
+    fn render_leaves_other_placeholders() {
+        let content = "${env:HOME} ${player} ${slotsCount} %slots% $slots {slots}";
+
+        assert_eq!(variables().render(content), Ok(content.to_string()));
+    }
+
+    #[test]
+    fn render_unescapes_literals() {
+        assert_eq!(
+            variables().render("$${slots} $${env:HOME} ${slots}"),
+            Ok("${slots} ${env:HOME} 20".to_string())
+        );
+    }
+
+    #[test]
+    fn render_reports_every_unresolved_variable() {
+        let unresolved = variables()
+            .render("${labels.b} ${slots} ${template.a} ${labels.b} ${other}")
+            .unwrap_err();
+
+        assert_eq!(
+            unresolved.into_iter().collect::<Vec<String>>(),
+            vec!["labels.b", "template.a"]
+        );
+    }
+
+    #[test]
+    fn render_keeps_unterminated_placeholders() {
+        assert_eq!(
+            variables().render("a ${slots} ${slots"),
+            Ok("a 20 ${slots".to_string())
+        );
+    }
+
+    #[test]
+    fn is_text_file_checks_the_extension() {
+        assert!(is_text_file("config/paper.yml"));
+        assert!(!is_text_file("plugins/plugin.jar"));
+        assert!(!is_text_file("log4j2.xml"));
+        assert!(!is_text_file("properties"));
+    }
+}